@@ -0,0 +1,606 @@
+//! Element by element deserialization of collections, recording the error of every element
+//! under its index (`[3]`) or map key instead of stopping at the first one.
+//!
+//! Elements and map values are buffered in a [`Content`] before being deserialized, so that the
+//! input of an invalid one is consumed whole and the next one can still be read. Their errors come
+//! from the buffer, hence carry no [`Location`](crate::Location).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+use serde::de::{self, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, Visitor};
+use serde::Deserialize;
+
+use crate::__private::{emit_warnings, nest_warnings};
+use crate::content::{Content, ContentDeserializer};
+use crate::{FieldError, StructValidator};
+
+/// Collections whose elements are deserialized one by one.
+pub trait Collect<'de>: Sized {
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>;
+}
+
+/// Seed deserializing a [`Collect`] collection.
+pub struct Collected<C>(pub PhantomData<C>);
+
+impl<'de, C> DeserializeSeed<'de> for Collected<C>
+where
+    C: Collect<'de>,
+{
+    type Value = C;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<C, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        C::collect(deserializer)
+    }
+}
+
+impl<'de, T> Collect<'de> for Vec<T>
+where
+    T: Deserialize<'de>,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+impl<'de, T> Collect<'de> for VecDeque<T>
+where
+    T: Deserialize<'de>,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+impl<'de, T, S> Collect<'de> for HashSet<T, S>
+where
+    T: Deserialize<'de> + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+impl<'de, T> Collect<'de> for BTreeSet<T>
+where
+    T: Deserialize<'de> + Ord,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+impl<'de, T> Collect<'de> for Box<[T]>
+where
+    T: Deserialize<'de>,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::collect(deserializer).map(Vec::into_boxed_slice)
+    }
+}
+
+impl<'de, T, const N: usize> Collect<'de> for [T; N]
+where
+    T: Deserialize<'de>,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let elements: Vec<T> = deserializer.deserialize_tuple(N, SeqVisitor(PhantomData))?;
+        let len = elements.len();
+        <[T; N]>::try_from(elements).map_err(|_| {
+            StructValidator::new()
+                .with_field_error(
+                    StructValidator::ROOT,
+                    FieldError::invalid_length(len, &format!("an array of length {}", N)),
+                )
+                .into_de_error()
+        })
+    }
+}
+
+impl<'de, K, V, S> Collect<'de> for HashMap<K, V, S>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+impl<'de, K, V> Collect<'de> for BTreeMap<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+/// An absent collection, or one collected element by element.
+impl<'de, C> Collect<'de> for Option<C>
+where
+    C: Collect<'de>,
+{
+    fn collect<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor(PhantomData))
+    }
+}
+
+struct OptionVisitor<C>(PhantomData<C>);
+
+impl<'de, C> Visitor<'de> for OptionVisitor<C>
+where
+    C: Collect<'de>,
+{
+    type Value = Option<C>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("option")
+    }
+
+    fn visit_none<E>(self) -> Result<Option<C>, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Option<C>, E>
+    where
+        E: de::Error,
+    {
+        Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Option<C>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        C::collect(deserializer).map(Some)
+    }
+}
+
+/// Outcome of reading one element, telling apart the errors of the element itself, after which
+/// the next one can be read, from errors of the enclosing sequence or map (e.g. syntax errors),
+/// which end the collection.
+enum Read<T, E> {
+    Value(T),
+    End,
+    Invalid(E),
+    Broken(E),
+}
+
+fn read<T, E, F>(next: F) -> Read<T, E>
+where
+    F: FnOnce(&mut bool) -> Result<Option<T>, E>,
+{
+    let mut entered = false;
+    match next(&mut entered) {
+        Ok(Some(value)) => Read::Value(value),
+        Ok(None) => Read::End,
+        Err(error) if entered => Read::Invalid(error),
+        Err(error) => Read::Broken(error),
+    }
+}
+
+/// Deserializes the buffered `content` of an element, if any was read.
+fn replay<'de, T, E>(content: Result<Option<Content<'de>>, E>) -> Read<T, E>
+where
+    T: Deserialize<'de>,
+    E: de::Error,
+{
+    match content {
+        Ok(Some(content)) => match T::deserialize(ContentDeserializer::new(content)) {
+            Ok(value) => Read::Value(value),
+            Err(error) => Read::Invalid(error),
+        },
+        Ok(None) => Read::End,
+        Err(error) => Read::Broken(error),
+    }
+}
+
+/// Seed flagging when the deserializer reached the element itself.
+struct Entered<'a, T> {
+    entered: &'a mut bool,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T> Entered<'a, T> {
+    fn new(entered: &'a mut bool) -> Self {
+        Entered {
+            entered,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, T> DeserializeSeed<'de> for Entered<'_, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        *self.entered = true;
+        T::deserialize(deserializer)
+    }
+}
+
+struct SeqVisitor<C, T>(PhantomData<(C, T)>);
+
+impl<'de, C, T> Visitor<'de> for SeqVisitor<C, T>
+where
+    C: Default + Extend<T>,
+    T: Deserialize<'de>,
+{
+    type Value = C;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<C, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        let mut elements = C::default();
+        let mut errors = StructValidator::new();
+        for index in 0.. {
+            let element = nest_warnings(&mut errors, format!("[{}]", index), || {
+                replay(seq.next_element())
+            });
+            match element {
+                Read::Value(element) => elements.extend(Some(element)),
+                Read::End => break,
+                Read::Invalid(error) => errors.insert_error(format!("[{}]", index), error),
+                Read::Broken(error) => return Err(error),
+            }
+        }
+        if errors.is_empty() {
+            emit_warnings(errors);
+            Ok(elements)
+        } else {
+            Err(errors.into_de_error())
+        }
+    }
+}
+
+struct MapVisitor<C, K, V>(PhantomData<(C, K, V)>);
+
+impl<'de, C, K, V> Visitor<'de> for MapVisitor<C, K, V>
+where
+    C: Default + Extend<(K, V)>,
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = C;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<C, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        let mut entries = C::default();
+        let mut errors = StructValidator::new();
+        for index in 0.. {
+            let mut text = None;
+            let key = read(|entered| {
+                map.next_key_seed(CaptureKey {
+                    seed: Entered::<K>::new(entered),
+                    text: &mut text,
+                })
+            });
+            // Entries are reported under their key, or their position if it isn't printable.
+            let path = text.unwrap_or_else(|| format!("[{}]", index));
+            let key = match key {
+                Read::Value(key) => key,
+                Read::End => break,
+                Read::Invalid(error) => {
+                    errors.insert_error(path, error);
+                    map.next_value::<IgnoredAny>()?;
+                    continue;
+                }
+                Read::Broken(error) => return Err(error),
+            };
+            let value = nest_warnings(&mut errors, path.clone(), || {
+                replay(map.next_value().map(Some))
+            });
+            match value {
+                Read::Value(value) => entries.extend(Some((key, value))),
+                Read::End => {}
+                Read::Invalid(error) => errors.insert_error(path, error),
+                Read::Broken(error) => return Err(error),
+            }
+        }
+        if errors.is_empty() {
+            emit_warnings(errors);
+            Ok(entries)
+        } else {
+            Err(errors.into_de_error())
+        }
+    }
+}
+
+/// Seed recording the text of a map key as it's deserialized, whatever the type of the key.
+struct CaptureKey<'a, S> {
+    seed: S,
+    text: &'a mut Option<String>,
+}
+
+impl<'de, S> DeserializeSeed<'de> for CaptureKey<'_, S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = S::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<S::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.seed.deserialize(Capture {
+            inner: deserializer,
+            text: self.text,
+        })
+    }
+}
+
+/// Deserializer, visitor and enum access forwarding everything to `inner`, recording the text
+/// of the scalars going through.
+struct Capture<'a, T> {
+    inner: T,
+    text: &'a mut Option<String>,
+}
+
+impl<'a, T> Capture<'a, T> {
+    fn wrap<U>(self, inner: U) -> (T, Capture<'a, U>) {
+        (
+            self.inner,
+            Capture {
+                inner,
+                text: self.text,
+            },
+        )
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let (inner, visitor) = self.wrap(visitor);
+                inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D> Deserializer<'de> for Capture<'_, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    forward_deserialize! {
+        deserialize_any()
+        deserialize_bool()
+        deserialize_i8()
+        deserialize_i16()
+        deserialize_i32()
+        deserialize_i64()
+        deserialize_i128()
+        deserialize_u8()
+        deserialize_u16()
+        deserialize_u32()
+        deserialize_u64()
+        deserialize_u128()
+        deserialize_f32()
+        deserialize_f64()
+        deserialize_char()
+        deserialize_str()
+        deserialize_string()
+        deserialize_bytes()
+        deserialize_byte_buf()
+        deserialize_option()
+        deserialize_unit()
+        deserialize_unit_struct(name: &'static str)
+        deserialize_newtype_struct(name: &'static str)
+        deserialize_seq()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_identifier()
+        deserialize_ignored_any()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! capture_visit {
+    ($($method:ident($ty:ty))*) => {
+        $(
+            fn $method<E>(self, value: $ty) -> Result<V::Value, E>
+            where
+                E: de::Error,
+            {
+                *self.text = Some(value.to_string());
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'de, V> Visitor<'de> for Capture<'_, V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    capture_visit! {
+        visit_bool(bool)
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_f32(f32)
+        visit_f64(f64)
+        visit_char(char)
+        visit_str(&str)
+        visit_borrowed_str(&'de str)
+        visit_string(String)
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        *self.text = Some(String::from_utf8_lossy(value).into_owned());
+        self.inner.visit_bytes(value)
+    }
+
+    fn visit_borrowed_bytes<E>(self, value: &'de [u8]) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        *self.text = Some(String::from_utf8_lossy(value).into_owned());
+        self.inner.visit_borrowed_bytes(value)
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        *self.text = Some(String::from_utf8_lossy(&value).into_owned());
+        self.inner.visit_byte_buf(value)
+    }
+
+    fn visit_none<E>(self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.inner.visit_none()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (inner, deserializer) = self.wrap(deserializer);
+        inner.visit_some(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.inner.visit_unit()
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (inner, deserializer) = self.wrap(deserializer);
+        inner.visit_newtype_struct(deserializer)
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<V::Value, A::Error>
+    where
+        A: de::SeqAccess<'de>,
+    {
+        self.inner.visit_seq(seq)
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<V::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        self.inner.visit_map(map)
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<V::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let (inner, data) = self.wrap(data);
+        inner.visit_enum(data)
+    }
+}
+
+impl<'de, A> EnumAccess<'de> for Capture<'_, A>
+where
+    A: EnumAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = A::Variant;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, A::Variant), A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.inner.variant_seed(CaptureKey {
+            seed,
+            text: self.text,
+        })
+    }
+}
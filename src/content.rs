@@ -0,0 +1,467 @@
+//! Buffering of the input of internally tagged, adjacently tagged and untagged enums, which have
+//! to read the whole value before picking the variant. Unlike a `serde_json::Value`, the buffer
+//! keeps the strings and bytes borrowed from the input, and holds whatever the format produced.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, Deserialize, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    Unexpected, VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+/// A value read from any self-describing format, replayed by [`ContentDeserializer`].
+#[derive(Clone, Debug)]
+pub enum Content<'de> {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+    Str(&'de str),
+    ByteBuf(Vec<u8>),
+    Bytes(&'de [u8]),
+    None,
+    Some(Box<Content<'de>>),
+    Unit,
+    Newtype(Box<Content<'de>>),
+    Seq(Vec<Content<'de>>),
+    Map(Vec<(Content<'de>, Content<'de>)>),
+}
+
+impl<'de> Content<'de> {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Content::String(string) => Some(string),
+            Content::Str(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Content::Bool(value) => Unexpected::Bool(*value),
+            Content::U8(value) => Unexpected::Unsigned(u64::from(*value)),
+            Content::U16(value) => Unexpected::Unsigned(u64::from(*value)),
+            Content::U32(value) => Unexpected::Unsigned(u64::from(*value)),
+            Content::U64(value) => Unexpected::Unsigned(*value),
+            Content::I8(value) => Unexpected::Signed(i64::from(*value)),
+            Content::I16(value) => Unexpected::Signed(i64::from(*value)),
+            Content::I32(value) => Unexpected::Signed(i64::from(*value)),
+            Content::I64(value) => Unexpected::Signed(*value),
+            Content::F32(value) => Unexpected::Float(f64::from(*value)),
+            Content::F64(value) => Unexpected::Float(*value),
+            Content::Char(value) => Unexpected::Char(*value),
+            Content::String(value) => Unexpected::Str(value),
+            Content::Str(value) => Unexpected::Str(value),
+            Content::ByteBuf(value) => Unexpected::Bytes(value),
+            Content::Bytes(value) => Unexpected::Bytes(value),
+            Content::None | Content::Some(_) => Unexpected::Option,
+            Content::Unit => Unexpected::Unit,
+            Content::Newtype(_) => Unexpected::NewtypeStruct,
+            Content::Seq(_) => Unexpected::Seq,
+            Content::Map(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Content<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ContentVisitor)
+    }
+}
+
+struct ContentVisitor;
+
+macro_rules! visit_primitives {
+    ($($method:ident($ty:ty) => $variant:ident,)*) => {
+        $(
+            fn $method<E>(self, value: $ty) -> Result<Content<'de>, E>
+            where
+                E: de::Error,
+            {
+                Ok(Content::$variant(value))
+            }
+        )*
+    };
+}
+
+impl<'de> Visitor<'de> for ContentVisitor {
+    type Value = Content<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    visit_primitives! {
+        visit_bool(bool) => Bool,
+        visit_u8(u8) => U8,
+        visit_u16(u16) => U16,
+        visit_u32(u32) => U32,
+        visit_u64(u64) => U64,
+        visit_i8(i8) => I8,
+        visit_i16(i16) => I16,
+        visit_i32(i32) => I32,
+        visit_i64(i64) => I64,
+        visit_f32(f32) => F32,
+        visit_f64(f64) => F64,
+        visit_char(char) => Char,
+        visit_string(String) => String,
+        visit_borrowed_str(&'de str) => Str,
+        visit_byte_buf(Vec<u8>) => ByteBuf,
+        visit_borrowed_bytes(&'de [u8]) => Bytes,
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Content<'de>, E>
+    where
+        E: de::Error,
+    {
+        Ok(Content::String(value.to_string()))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Content<'de>, E>
+    where
+        E: de::Error,
+    {
+        Ok(Content::ByteBuf(value.to_vec()))
+    }
+
+    fn visit_none<E>(self) -> Result<Content<'de>, E>
+    where
+        E: de::Error,
+    {
+        Ok(Content::None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Content<'de>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Content::deserialize(deserializer).map(|content| Content::Some(Box::new(content)))
+    }
+
+    fn visit_unit<E>(self) -> Result<Content<'de>, E>
+    where
+        E: de::Error,
+    {
+        Ok(Content::Unit)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Content<'de>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Content::deserialize(deserializer).map(|content| Content::Newtype(Box::new(content)))
+    }
+
+    fn visit_seq<V>(self, mut seq: V) -> Result<Content<'de>, V::Error>
+    where
+        V: SeqAccess<'de>,
+    {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Content::Seq(elements))
+    }
+
+    fn visit_map<V>(self, mut map: V) -> Result<Content<'de>, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Content::Map(entries))
+    }
+
+    fn visit_enum<A>(self, _: A) -> Result<Content<'de>, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        Err(de::Error::custom(
+            "internally tagged, adjacently tagged and untagged enums do not support enum input",
+        ))
+    }
+}
+
+/// The entries of a buffered map, such as an internally or adjacently tagged enum.
+pub struct ContentMap<'de>(Vec<(Content<'de>, Content<'de>)>);
+
+impl<'de> ContentMap<'de> {
+    /// Removes the entry of the string key `key`, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<Content<'de>> {
+        let index = self.0.iter().position(|(k, _)| k.as_str() == Some(key))?;
+        Some(self.0.remove(index).1)
+    }
+
+    /// The remaining entries, as a map.
+    pub fn into_content(self) -> Content<'de> {
+        Content::Map(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentMap<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ContentMapVisitor)
+    }
+}
+
+struct ContentMapVisitor;
+
+impl<'de> Visitor<'de> for ContentMapVisitor {
+    type Value = ContentMap<'de>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<V>(self, map: V) -> Result<ContentMap<'de>, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        match ContentVisitor.visit_map(map)? {
+            Content::Map(entries) => Ok(ContentMap(entries)),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Deserializer replaying a [`Content`], failing with the error type `E` of the deserializer
+/// it was read from.
+pub struct ContentDeserializer<'de, E> {
+    content: Content<'de>,
+    marker: PhantomData<E>,
+}
+
+impl<'de, E> ContentDeserializer<'de, E> {
+    pub fn new(content: Content<'de>) -> Self {
+        Self {
+            content,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, E> IntoDeserializer<'de, E> for Content<'de>
+where
+    E: de::Error,
+{
+    type Deserializer = ContentDeserializer<'de, E>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        ContentDeserializer::new(self)
+    }
+}
+
+impl<'de, E> Deserializer<'de> for ContentDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::Bool(value) => visitor.visit_bool(value),
+            Content::U8(value) => visitor.visit_u8(value),
+            Content::U16(value) => visitor.visit_u16(value),
+            Content::U32(value) => visitor.visit_u32(value),
+            Content::U64(value) => visitor.visit_u64(value),
+            Content::I8(value) => visitor.visit_i8(value),
+            Content::I16(value) => visitor.visit_i16(value),
+            Content::I32(value) => visitor.visit_i32(value),
+            Content::I64(value) => visitor.visit_i64(value),
+            Content::F32(value) => visitor.visit_f32(value),
+            Content::F64(value) => visitor.visit_f64(value),
+            Content::Char(value) => visitor.visit_char(value),
+            Content::String(value) => visitor.visit_string(value),
+            Content::Str(value) => visitor.visit_borrowed_str(value),
+            Content::ByteBuf(value) => visitor.visit_byte_buf(value),
+            Content::Bytes(value) => visitor.visit_borrowed_bytes(value),
+            Content::None => visitor.visit_none(),
+            Content::Some(value) => visitor.visit_some(ContentDeserializer::new(*value)),
+            Content::Unit => visitor.visit_unit(),
+            Content::Newtype(value) => {
+                visitor.visit_newtype_struct(ContentDeserializer::new(*value))
+            }
+            Content::Seq(elements) => {
+                let mut seq = SeqDeserializer::new(elements.into_iter());
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Content::Map(entries) => {
+                let mut map = MapDeserializer::new(entries.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::None | Content::Unit => visitor.visit_none(),
+            Content::Some(value) => visitor.visit_some(ContentDeserializer::new(*value)),
+            content => visitor.visit_some(ContentDeserializer::new(content)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.content {
+            Content::Newtype(value) => {
+                visitor.visit_newtype_struct(ContentDeserializer::new(*value))
+            }
+            content => visitor.visit_newtype_struct(ContentDeserializer::new(content)),
+        }
+    }
+
+    /// Unit variants are strings, other variants maps with a single entry.
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        let (variant, value) = match self.content {
+            Content::Map(mut entries) if entries.len() == 1 => {
+                let (variant, value) = entries.remove(0);
+                (variant, Some(value))
+            }
+            content @ (Content::String(_) | Content::Str(_)) => (content, None),
+            content => {
+                return Err(de::Error::invalid_type(
+                    content.unexpected(),
+                    &"a string or a map with a single key",
+                ))
+            }
+        };
+        visitor.visit_enum(EnumDeserializer {
+            variant,
+            value,
+            marker: PhantomData,
+        })
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier
+    }
+}
+
+struct EnumDeserializer<'de, E> {
+    variant: Content<'de>,
+    value: Option<Content<'de>>,
+    marker: PhantomData<E>,
+}
+
+impl<'de, E> EnumAccess<'de> for EnumDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+    type Variant = VariantDeserializer<'de, E>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), E>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(ContentDeserializer::new(self.variant))?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                value: self.value,
+                marker: PhantomData,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer<'de, E> {
+    value: Option<Content<'de>>,
+    marker: PhantomData<E>,
+}
+
+impl<'de, E> VariantDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    fn into_value(self, expected: &str) -> Result<ContentDeserializer<'de, E>, E> {
+        match self.value {
+            Some(value) => Ok(ContentDeserializer::new(value)),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &expected)),
+        }
+    }
+}
+
+impl<'de, E> VariantAccess<'de> for VariantDeserializer<'de, E>
+where
+    E: de::Error,
+{
+    type Error = E;
+
+    fn unit_variant(self) -> Result<(), E> {
+        match self.value {
+            Some(value) => de::Deserialize::deserialize(ContentDeserializer::new(value)),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, E>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.into_value("newtype variant")?)
+    }
+
+    fn tuple_variant<V>(self, _: usize, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.into_value("tuple variant")?.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V>(self, _: &'static [&'static str], visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        self.into_value("struct variant")?.deserialize_map(visitor)
+    }
+}
@@ -0,0 +1,262 @@
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Missing,
+    InvalidType,
+    InvalidValue,
+    InvalidLength,
+    UnknownVariant,
+    UnknownField,
+    /// Fields that can't be set together, see `#[validate(at_most_one_of(..))]`.
+    Conflict,
+    Custom,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Display, Serialize, Deserialize)]
+#[display(fmt = "{}", message)]
+pub struct FieldError {
+    pub kind: ErrorKind,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+}
+
+/// Position in the source document, as reported by the deserializer for the field's error. Only
+/// the position a format puts in its error messages is known, so it isn't always the start of the
+/// value:
+///
+/// - serde_yaml points at the start of the offending value;
+/// - serde_json points at its last character;
+/// - toml points at the start of the value for the error of a whole document, and reports no
+///   position for the fields of a document deserialized with a derived type.
+///
+/// Errors of the validation rules, and of collection elements, which are read back from a buffer,
+/// have no position whatever the format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
+#[display(fmt = "line {} column {}", line, column)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl FieldError {
+    pub fn new<T>(kind: ErrorKind, message: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            kind,
+            message: message.into(),
+            expected: None,
+            actual: None,
+            location: None,
+        }
+    }
+
+    pub fn missing() -> Self {
+        Self::new(ErrorKind::Missing, "field is missing")
+    }
+
+    pub fn unknown_variant(variant: &str, expected: &[&str]) -> Self {
+        let expected = match expected {
+            [] => "there are no variants".to_string(),
+            [variant] => format!("`{}`", variant),
+            _ => format!(
+                "one of {}",
+                expected
+                    .iter()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        Self::new(
+            ErrorKind::UnknownVariant,
+            format!("unknown variant `{}`, expected {}", variant, expected),
+        )
+        .with_expected(expected)
+        .with_actual(format!("`{}`", variant))
+    }
+
+    /// `actual` describes the value the way serde does, e.g. ``integer `1` `` or `string "a"`.
+    pub fn invalid_value(actual: &str, expected: &str) -> Self {
+        Self::new(
+            ErrorKind::InvalidValue,
+            format!("invalid value: {}, expected {}", actual, expected),
+        )
+        .with_expected(expected)
+        .with_actual(actual)
+    }
+
+    pub fn invalid_length(len: usize, expected: &str) -> Self {
+        Self::new(
+            ErrorKind::InvalidLength,
+            format!("invalid length {}, expected {}", len, expected),
+        )
+        .with_expected(expected)
+        .with_actual(len.to_string())
+    }
+
+    /// Error for a key that doesn't match any of the `expected` fields, suggesting the closest
+    /// one when it looks like a typo.
+    pub fn unknown_field(field: &str, expected: &[&str]) -> Self {
+        let message = match suggest(field, expected) {
+            Some(suggestion) => format!("unknown field, did you mean `{}`?", suggestion),
+            None => "unknown field".to_string(),
+        };
+        let expected = expected
+            .iter()
+            .map(|name| format!("`{}`", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self::new(ErrorKind::UnknownField, message)
+            .with_expected(expected)
+            .with_actual(format!("`{}`", field))
+    }
+
+    pub fn custom<T>(message: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self::new(ErrorKind::Custom, message)
+    }
+
+    pub fn with_expected<T>(mut self, expected: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.expected = Some(expected.into());
+        self
+    }
+
+    pub fn with_actual<T>(mut self, actual: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.actual = Some(actual.into());
+        self
+    }
+
+    pub fn with_location(mut self, line: usize, column: usize) -> Self {
+        self.location = Some(Location { line, column });
+        self
+    }
+
+    /// Classifies a message produced by one of the `serde::de::Error` constructors. Anything
+    /// that doesn't follow serde's wording is a `Custom` error. Decorations added by the
+    /// deserializer around serde's wording, such as serde_yaml's leading `path: `, are dropped.
+    pub fn from_message<T>(message: T) -> Self
+    where
+        T: Into<String>,
+    {
+        let message = message.into();
+        if let Some(error) = parse_message(&message) {
+            return error;
+        }
+        match message.split_once(": ") {
+            Some((path, rest)) if !path.contains(char::is_whitespace) => parse_message(rest),
+            _ => None,
+        }
+        .unwrap_or_else(|| Self::custom(message))
+    }
+}
+
+type Parsed = (ErrorKind, Option<String>, Option<String>);
+
+fn parse_message(message: &str) -> Option<FieldError> {
+    // Some deserializers append context lines (e.g. toml's "in `field`") after serde's message.
+    let message = message.lines().next().unwrap_or_default();
+    let parsed: Option<Parsed> =
+        if message == "field is missing" || message.starts_with("missing field `") {
+            Some((ErrorKind::Missing, None, None))
+        } else if let Some(rest) = message.strip_prefix("invalid type: ") {
+            split_expected(rest, ", expected ")
+                .map(|(actual, expected)| (ErrorKind::InvalidType, Some(actual), Some(expected)))
+        } else if let Some(rest) = message.strip_prefix("invalid value: ") {
+            split_expected(rest, ", expected ")
+                .map(|(actual, expected)| (ErrorKind::InvalidValue, Some(actual), Some(expected)))
+        } else if let Some(rest) = message.strip_prefix("invalid length ") {
+            split_expected(rest, ", expected ")
+                .map(|(actual, expected)| (ErrorKind::InvalidLength, Some(actual), Some(expected)))
+        } else if let Some(rest) = message.strip_prefix("unknown variant ") {
+            split_expected(rest, ", expected ")
+                .or_else(|| split_expected(rest, ", "))
+                .map(|(actual, expected)| (ErrorKind::UnknownVariant, Some(actual), Some(expected)))
+        } else if let Some(rest) = message.strip_prefix("unknown field ") {
+            split_expected(rest, ", expected ")
+                .or_else(|| split_expected(rest, ", "))
+                .map(|(actual, expected)| (ErrorKind::UnknownField, Some(actual), Some(expected)))
+        } else {
+            None
+        };
+    parsed.map(|(kind, actual, expected)| FieldError {
+        kind,
+        message: message.to_string(),
+        expected,
+        actual,
+        location: None,
+    })
+}
+
+fn suggest<'a>(field: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    let max_distance = std::cmp::max(1, field.chars().count() / 3);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(field, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance where swapping two adjacent characters counts as a single edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+fn split_expected(rest: &str, separator: &str) -> Option<(String, String)> {
+    let index = rest.find(separator)?;
+    Some((
+        rest[..index].to_string(),
+        rest[index + separator.len()..].to_string(),
+    ))
+}
+
+impl From<String> for FieldError {
+    fn from(message: String) -> Self {
+        Self::from_message(message)
+    }
+}
+
+impl From<&str> for FieldError {
+    fn from(message: &str) -> Self {
+        Self::from_message(message)
+    }
+}
@@ -1,37 +1,88 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt::Display;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
 use std::iter::Extend;
 use std::iter::FromIterator;
+use std::sync::OnceLock;
 
-use derive_more::{Display, Error, From, IntoIterator};
+use derive_more::{Display, Error, IntoIterator};
 use serde::{Deserialize, Serialize};
 
+#[doc(hidden)]
+#[path = "private.rs"]
+pub mod __private;
+mod collect;
+mod content;
+mod field_error;
+pub mod rules;
+
+pub use field_error::{ErrorKind, FieldError, Location};
 pub use paste;
+pub use struct_validator_derive::{Validate, ValidatedDeserialize};
 
-#[derive(Clone, Display, Error, Debug, Default, IntoIterator, From, Serialize, Deserialize)]
+#[derive(Clone, Display, Error, Debug, Default, IntoIterator, Serialize, Deserialize)]
 #[display(fmt = "{}", "self.to_json_string()")]
 pub struct StructValidator {
-    pub errors: HashMap<String, String>,
+    /// Errors of each field, in the order they were recorded. Serialized as a list of
+    /// [`FieldError`] objects per field, `{"errors": {"name": [{"kind": "missing", "message":
+    /// "field is missing"}]}}`, where it used to be a single message string.
+    #[into_iterator(owned)]
+    pub errors: BTreeMap<String, Vec<FieldError>>,
+    /// Non-fatal findings (e.g. unknown fields), they don't make the validator fail. Values that
+    /// deserialize successfully log theirs, unless read with [`deserialize_with_warnings`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub warnings: BTreeMap<String, Vec<FieldError>>,
 }
 
 impl StructValidator {
+    /// Key for errors that concern the value as a whole rather than one of its fields. Nested
+    /// under a field, they become errors of that field.
+    pub const ROOT: &'static str = "__all__";
+
     pub fn new() -> Self {
         Self {
-            errors: HashMap::new(),
+            errors: BTreeMap::new(),
+            warnings: BTreeMap::new(),
         }
     }
 
-    fn from_json_string<T>(str: T) -> Result<Self, serde_json::Error>
+    /// Wraps the validator in a deserializer error, so it can be carried out of a `Deserialize`
+    /// impl and recovered losslessly with [`StructValidator::from_de_error`]. The message holds
+    /// the validator as JSON, after a marker with a nonce drawn once per process, so text from the
+    /// input can't pass for a validator.
+    pub fn into_de_error<E>(self) -> E
     where
-        T: Into<String>,
+        E: serde::de::Error,
+    {
+        E::custom(format_args!("{}{}", marker(), self.to_json_string()))
+    }
+
+    /// Recovers the validator carried by an error built with [`StructValidator::into_de_error`],
+    /// whatever text the deserializer wrapped around it. The error is left untouched, so it can
+    /// be recovered again, on any thread of the process.
+    pub fn from_de_error<E>(error: &E) -> Option<Self>
+    where
+        E: Display + ?Sized,
     {
-        let error_str = str.into();
-        let line_info_index = error_str
-            .rfind(" at line")
-            .unwrap_or_else(|| error_str.len());
-        let error_str = &error_str[..line_info_index];
-        serde_json::from_str(error_str)
+        let message = error.to_string();
+        let start = message.find(marker())? + marker().len();
+        serde_json::Deserializer::from_str(&message[start..])
+            .into_iter()
+            .next()?
+            .ok()
+    }
+
+    /// Same as [`StructValidator::from_de_error`], giving the error back when it doesn't carry
+    /// a validator. Works with the error type of any deserializer (serde_json, serde_yaml, toml...).
+    pub fn try_from_de_error<E>(error: E) -> Result<Self, E>
+    where
+        E: serde::de::Error,
+    {
+        Self::from_de_error(&error).ok_or(error)
     }
 
     pub fn to_json_string(&self) -> String {
@@ -39,17 +90,99 @@ impl StructValidator {
             .unwrap_or_else(|e| format!("Error serializing StructValidator: {}", e))
     }
 
+    /// Appends an error with the given message to the errors of `key`.
     pub fn insert<K, V>(&mut self, key: K, value: V)
     where
         K: Into<String>,
         V: Into<String>,
     {
         let error_str = value.into();
-        let line_info_index = error_str
-            .rfind(" at line")
-            .unwrap_or_else(|| error_str.len());
-        let error_str = error_str[..line_info_index].to_string();
-        self.errors.insert(key.into(), error_str);
+        let (error_str, location) = split_location(&error_str);
+        let mut error = FieldError::from_message(error_str);
+        error.location = location;
+        self.insert_field_error(key, error);
+    }
+
+    pub fn insert_field_error<K>(&mut self, key: K, error: FieldError)
+    where
+        K: Into<String>,
+    {
+        self.errors.entry(key.into()).or_default().push(error);
+    }
+
+    /// Records the error of a field. When the error carries the errors of a nested validated
+    /// struct, they are merged under the field's path (e.g. `address.zip`) instead.
+    pub fn insert_error<K, E>(&mut self, key: K, error: E)
+    where
+        K: Into<String>,
+        E: Display,
+    {
+        match Self::from_de_error(&error) {
+            Some(nested) => self.extend_nested(key, nested),
+            None => self.insert(key, error.to_string()),
+        }
+    }
+
+    pub fn insert_warning<K>(&mut self, key: K, warning: FieldError)
+    where
+        K: Into<String>,
+    {
+        self.warnings.entry(key.into()).or_default().push(warning);
+    }
+
+    /// Adds the errors of `nested` under the `prefix` path: `prefix.field` for fields,
+    /// `prefix[3]` for elements of a sequence.
+    pub fn extend_nested<K>(&mut self, prefix: K, nested: StructValidator)
+    where
+        K: Into<String>,
+    {
+        let prefix = prefix.into();
+        let nest = |(key, value): (String, _)| {
+            let path = if prefix == Self::ROOT {
+                key
+            } else if key == Self::ROOT {
+                prefix.clone()
+            } else if key.starts_with('[') {
+                format!("{}{}", prefix, key)
+            } else {
+                format!("{}.{}", prefix, key)
+            };
+            (path, value)
+        };
+        self.merge(StructValidator {
+            errors: nested.errors.into_iter().map(nest).collect(),
+            warnings: nested.warnings.into_iter().map(nest).collect(),
+        });
+    }
+
+    /// Adds the errors and warnings of `other` to the ones already recorded.
+    pub fn merge(&mut self, other: StructValidator) {
+        self.extend(other.errors);
+        for (key, warnings) in other.warnings {
+            self.warnings.entry(key).or_default().extend(warnings);
+        }
+    }
+
+    /// Same as [`merge`](Self::merge), dropping the errors of `other` for keys that already have
+    /// errors (see [`contains`](Self::contains)), so a field is only blamed for its first failure.
+    pub fn merge_unfailed(&mut self, other: StructValidator) {
+        let errors: Vec<_> = other
+            .errors
+            .into_iter()
+            .filter(|(key, _)| key == Self::ROOT || !self.contains(key.as_str()))
+            .collect();
+        self.merge(StructValidator {
+            errors: errors.into_iter().collect(),
+            warnings: other.warnings,
+        });
+    }
+
+    pub fn log_warnings(&self) {
+        for (key, warnings) in &self.warnings {
+            for warning in warnings {
+                log::warn!("{}: {}", key, warning);
+            }
+        }
     }
 
     pub fn with<K, V>(mut self, key: K, value: V) -> Self
@@ -61,6 +194,14 @@ impl StructValidator {
         self
     }
 
+    pub fn with_field_error<K>(mut self, key: K, error: FieldError) -> Self
+    where
+        K: Into<String>,
+    {
+        self.insert_field_error(key, error);
+        self
+    }
+
     pub fn with_result<K, V, T>(self, key: K, result: Result<T, V>) -> Self
     where
         K: Into<String>,
@@ -73,23 +214,155 @@ impl StructValidator {
         }
     }
 
+    /// Whether there are errors for `key`, either directly or for any path nested under it.
     pub fn contains<T>(&self, key: T) -> bool
     where
         T: Into<String>,
     {
-        self.errors.contains_key(&key.into())
+        let key = key.into();
+        self.errors.contains_key(&key)
+            || self.errors.keys().any(|k| {
+                k.strip_prefix(key.as_str())
+                    .is_some_and(|rest| rest.starts_with('.') || rest.starts_with('['))
+            })
+    }
+
+    /// The errors and warnings under the `key` path, relative to it: the ones of `key` itself
+    /// end up under [`ROOT`](Self::ROOT). The inverse of [`extend_nested`](Self::extend_nested).
+    pub fn nested<K>(&self, key: K) -> StructValidator
+    where
+        K: Into<String>,
+    {
+        let key = key.into();
+        let relative = |path: &String| {
+            let rest = path.strip_prefix(key.as_str())?;
+            if rest.is_empty() {
+                Some(Self::ROOT.to_string())
+            } else if let Some(field) = rest.strip_prefix('.') {
+                Some(field.to_string())
+            } else if rest.starts_with('[') {
+                Some(rest.to_string())
+            } else {
+                None
+            }
+        };
+        let nest = |errors: &BTreeMap<String, Vec<FieldError>>| {
+            errors
+                .iter()
+                .filter_map(|(path, errors)| Some((relative(path)?, errors.clone())))
+                .collect()
+        };
+        StructValidator {
+            errors: nest(&self.errors),
+            warnings: nest(&self.warnings),
+        }
+    }
+
+    pub fn get<T>(&self, key: T) -> Option<&[FieldError]>
+    where
+        T: Into<String>,
+    {
+        self.errors.get(&key.into()).map(Vec::as_slice)
     }
 
     pub fn is_empty(&self) -> bool {
         self.errors.is_empty()
     }
+
+    /// Renders the errors as plain messages, the way they were reported before error kinds.
+    pub fn messages(&self) -> BTreeMap<String, Vec<String>> {
+        self.errors
+            .iter()
+            .map(|(key, errors)| {
+                let messages = errors.iter().map(|e| e.message.clone()).collect();
+                (key.clone(), messages)
+            })
+            .collect()
+    }
+}
+
+/// Prefix of the validators carried in error messages, `struct_validator errors <nonce>: `.
+fn marker() -> &'static str {
+    static MARKER: OnceLock<String> = OnceLock::new();
+    MARKER.get_or_init(|| {
+        // `RandomState` is seeded from the OS, which is all a nonce needs.
+        let nonce = RandomState::new().build_hasher().finish();
+        format!("struct_validator errors {:016x}: ", nonce)
+    })
+}
+
+thread_local! {
+    /// Warnings of the values deserialized so far under `deserialize_with_warnings`, `None`
+    /// outside of it.
+    static WARNINGS: RefCell<Option<StructValidator>> = const { RefCell::new(None) };
+}
+
+/// Splits off the ` at line X column Y` suffix serde_json and serde_yaml append to their messages,
+/// or the `TOML parse error at line X, column Y` header and source excerpt toml puts before them.
+fn split_location(message: &str) -> (&str, Option<Location>) {
+    if let Some(rest) = message.strip_prefix("TOML parse error at line ") {
+        return split_toml_location(message, rest);
+    }
+    let index = match message.rfind(" at line ") {
+        Some(index) => index,
+        None => return (message, None),
+    };
+    let mut parts = message[index + " at line ".len()..].splitn(3, ' ');
+    let location = match (parts.next(), parts.next(), parts.next()) {
+        (Some(line), Some("column"), Some(column)) => line
+            .parse()
+            .and_then(|line| {
+                Ok(Location {
+                    line,
+                    column: column.parse()?,
+                })
+            })
+            .ok(),
+        _ => None,
+    };
+    match location {
+        Some(location) => (&message[..index], Some(location)),
+        None => (message, None),
+    }
+}
+
+fn split_toml_location<'a>(message: &'a str, rest: &'a str) -> (&'a str, Option<Location>) {
+    let (position, body) = rest.split_once('\n').unwrap_or((rest, ""));
+    let location = position.split_once(", column ").and_then(|(line, column)| {
+        Some(Location {
+            line: line.parse().ok()?,
+            column: column.parse().ok()?,
+        })
+    });
+    // Lines of the excerpt start with `|`, after the line number if any.
+    let excerpt: usize = body
+        .split_inclusive('\n')
+        .take_while(|line| {
+            line.trim_start_matches(|c: char| c.is_ascii_digit() || c == ' ')
+                .starts_with('|')
+        })
+        .map(str::len)
+        .sum();
+    match location {
+        Some(location) => (body[excerpt..].trim_end(), Some(location)),
+        None => (message, None),
+    }
+}
+
+impl From<BTreeMap<String, Vec<FieldError>>> for StructValidator {
+    fn from(errors: BTreeMap<String, Vec<FieldError>>) -> Self {
+        Self {
+            errors,
+            warnings: BTreeMap::new(),
+        }
+    }
 }
 
 impl TryFrom<&serde_json::Error> for StructValidator {
     type Error = serde_json::Error;
 
     fn try_from(error: &serde_json::Error) -> Result<Self, serde_json::Error> {
-        Self::from_json_string(error.to_string())
+        Self::from_de_error(error).ok_or_else(|| serde::de::Error::custom(error))
     }
 }
 
@@ -97,7 +370,7 @@ impl TryFrom<serde_json::Error> for StructValidator {
     type Error = serde_json::Error;
 
     fn try_from(error: serde_json::Error) -> Result<Self, serde_json::Error> {
-        Self::from_json_string(error.to_string())
+        Self::try_from_de_error(error)
     }
 }
 
@@ -106,9 +379,11 @@ impl serde::de::Error for StructValidator {
     where
         T: Display,
     {
-        let mut errors = Self::new();
-        errors.insert("unknown".to_string(), msg.to_string());
-        errors
+        Self::from_de_error(&msg).unwrap_or_else(|| {
+            let mut errors = Self::new();
+            errors.insert("unknown".to_string(), msg.to_string());
+            errors
+        })
     }
 }
 
@@ -120,7 +395,7 @@ impl<'a, T: 'a> FromIterator<&'a Result<T, StructValidator>> for StructValidator
         let mut errors = Self::new();
         for item in iter {
             if let Err(e) = item {
-                errors.extend(e.clone());
+                errors.merge(e.clone());
             }
         }
         errors
@@ -132,7 +407,20 @@ impl Extend<(String, String)> for StructValidator {
     where
         T: IntoIterator<Item = (String, String)>,
     {
-        self.errors.extend(iter)
+        for (key, message) in iter {
+            self.insert(key, message);
+        }
+    }
+}
+
+impl Extend<(String, Vec<FieldError>)> for StructValidator {
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = (String, Vec<FieldError>)>,
+    {
+        for (key, messages) in iter {
+            self.errors.entry(key).or_default().extend(messages);
+        }
     }
 }
 
@@ -142,18 +430,107 @@ impl<'a, T: 'a> Extend<&'a Result<T, StructValidator>> for StructValidator {
         I: IntoIterator<Item = &'a Result<T, StructValidator>>,
     {
         let errors: StructValidator = iter.into_iter().collect();
-        self.extend(errors);
+        self.merge(errors);
+    }
+}
+
+/// Deserializes a `T`, returning the warnings of it and of the values nested in it (e.g. unknown
+/// fields under `#[validate(unknown_fields = "warn")]`) along with it. Values deserialized
+/// otherwise log their warnings, a failed deserialization carries them in its validator.
+pub fn deserialize_with_warnings<'de, T, D>(
+    deserializer: D,
+) -> Result<(T, StructValidator), D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    let (value, warnings) = __private::collect_warnings(|| T::deserialize(deserializer));
+    value.map(|value| (value, warnings))
+}
+
+/// Deserialization keeping the fields that could be deserialized alongside the errors of the
+/// others, implemented by `#[derive(ValidatedDeserialize)]` on structs marked
+/// `#[validate(partial)]`.
+pub trait DeserializePartial<'de>: Sized {
+    /// The generated `{Name}Partial` struct, holding every field as `Result<T, StructValidator>`
+    /// with the errors relative to the field.
+    type Partial;
+
+    /// Fails only if the input can't be read at all, otherwise every error is in the returned
+    /// validator.
+    fn deserialize_partial<D>(
+        deserializer: D,
+    ) -> Result<(Self::Partial, StructValidator), D::Error>
+    where
+        D: serde::Deserializer<'de>;
+}
+
+/// Semantic checks of an already deserialized value, implemented by `#[derive(Validate)]` from
+/// the `#[validate(...)]` rules of its fields. Errors are keyed like the deserialization ones.
+pub trait Validate {
+    fn validate(&self) -> Result<(), StructValidator>;
+}
+
+/// Validation with rules that need external data (e.g. the current user or settings loaded at
+/// startup), implemented by `#[derive(Validate)]` on structs with a `#[validate(context = "...")]`.
+pub trait ValidateWith<Ctx>: Validate
+where
+    Ctx: ?Sized,
+{
+    /// Runs the rules of [`Validate`], then the `custom_with` and `schema_with` rules, which
+    /// receive `ctx`. They are skipped for fields that already failed.
+    fn validate_with(&self, ctx: &Ctx) -> Result<(), StructValidator>;
+}
+
+/// Validation with rules that need I/O through a user-supplied context (e.g. a repository),
+/// implemented by `#[derive(Validate)]` on structs with a `#[validate(context = "...")]`.
+pub trait ValidateAsync<Ctx>: ValidateWith<Ctx>
+where
+    Ctx: ?Sized,
+{
+    /// Runs the rules of [`ValidateWith`], then the `async_custom` and `async_schema` rules
+    /// concurrently. Async rules of fields that already failed are skipped.
+    ///
+    /// The future is `Send` when the value and the context are `Sync` and the futures of the
+    /// async rules are `Send`, so it can be spawned on a multi-threaded runtime. Only code naming
+    /// the validated type can rely on it though: the trait doesn't require `Send`, to keep
+    /// single-threaded contexts (e.g. holding a `RefCell`) usable, so generic code bounded by
+    /// `ValidateAsync` alone has to await the future where it is.
+    fn validate_async<'a>(
+        &'a self,
+        ctx: &'a Ctx,
+    ) -> impl Future<Output = Result<(), StructValidator>> + 'a;
+}
+
+/// Elements are validated one by one, their errors keyed by position (e.g. `[3].price`).
+impl<T> Validate for Vec<T>
+where
+    T: Validate,
+{
+    fn validate(&self) -> Result<(), StructValidator> {
+        let mut errors = StructValidator::new();
+        for (i, element) in self.iter().enumerate() {
+            if let Err(nested) = element.validate() {
+                errors.extend_nested(format!("[{}]", i), nested);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 }
 
+/// Prefer `#[derive(ValidatedDeserialize)]`, which reads the fields from the struct definition.
 #[macro_export]
 macro_rules! deserialize_struct {
 ($struct_name:ident, [$($field_name:ident),*], $explanation:literal) => {
-	struct_validator::paste::item! {
-		impl<'de> serde::Deserialize<'de> for $struct_name {
+	$crate::paste::item! {
+		impl<'de> $crate::__private::serde::Deserialize<'de> for $struct_name {
 			fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
 			where
-				D: serde::Deserializer<'de>,
+				D: $crate::__private::serde::Deserializer<'de>,
 			{
 				#[allow(non_camel_case_types)]
 				enum Field {
@@ -163,13 +540,13 @@ macro_rules! deserialize_struct {
 
 				struct FieldVisitor;
 
-				impl <'de> serde::de::Visitor<'de> for FieldVisitor {
+				impl <'de> $crate::__private::serde::de::Visitor<'de> for FieldVisitor {
 					type Value = Field;
 					fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
 							write!(formatter, "a {}", stringify!($struct_name))
 					}
 					fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-					where E: serde::de::Error
+					where E: $crate::__private::serde::de::Error
 					{
 							match value {
 								$(stringify!($field_name) => Ok(Field::$field_name)),*,
@@ -178,12 +555,12 @@ macro_rules! deserialize_struct {
 					}
 				}
 
-				impl <'de> serde::Deserialize<'de> for Field {
+				impl <'de> $crate::__private::serde::Deserialize<'de> for Field {
 						#[inline]
 						fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-						where D: serde::Deserializer<'de>
+						where D: $crate::__private::serde::Deserializer<'de>
 						{
-								serde::Deserializer::deserialize_identifier(
+								$crate::__private::serde::Deserializer::deserialize_identifier(
 									deserializer,
 									FieldVisitor
 								)
@@ -192,7 +569,7 @@ macro_rules! deserialize_struct {
 
 				struct [<$struct_name Visitor>];
 
-				impl<'de> serde::de::Visitor<'de> for [<$struct_name Visitor>] {
+				impl<'de> $crate::__private::serde::de::Visitor<'de> for [<$struct_name Visitor>] {
 					type Value = $struct_name;
 
 					fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -201,7 +578,7 @@ macro_rules! deserialize_struct {
 
 					fn visit_map<V>(self, mut map: V) -> Result<$struct_name, V::Error>
 					where
-						V: serde::de::MapAccess<'de>,
+						V: $crate::__private::serde::de::MapAccess<'de>,
 					{
 						$(let mut $field_name = None;)*
 						let mut errors = $crate::StructValidator::new();
@@ -213,34 +590,27 @@ macro_rules! deserialize_struct {
 											$field_name = v;
 										},
 										Err(e) => {
-											errors.insert(stringify!($field_name), e.to_string());
+											errors.insert_error(stringify!($field_name), e);
 										}
 									}
 								})*,
 								_ => {
-									let _ = match map.next_value::<serde::de::IgnoredAny>() {
-										serde::export::Ok(__val) => __val,
-										serde::export::Err(__err) => {
-											return serde::export::Err(__err);
-										}
-									};
+									map.next_value::<$crate::__private::serde::de::IgnoredAny>()?;
 								}
 							}
 						}
 						$(
 							if $field_name.is_none() && ! errors.contains(stringify!($field_name)) {
-								errors.insert(stringify!($field_name), "field is missing")
+								errors.insert_field_error(stringify!($field_name), $crate::FieldError::missing())
 							}
 						)*
 
 						if (!errors.is_empty()) {
-							return Err(serde::de::Error::custom(
-								errors.to_json_string()
-							));
+							return Err(errors.into_de_error());
 						}
 						$(
 							let $field_name = $field_name.ok_or_else(
-								|| serde::de::Error::missing_field(stringify!($field_name))
+								|| $crate::__private::serde::de::Error::missing_field(stringify!($field_name))
 							)?;
 						)*
 						Ok($struct_name {
@@ -0,0 +1,147 @@
+//! Support code for the `ValidatedDeserialize` and `Validate` derives, not part of the public
+//! API.
+
+use std::fmt::Display;
+
+pub use serde;
+use serde::de;
+
+#[cfg(feature = "async")]
+pub use futures;
+#[cfg(feature = "regex")]
+pub use regex::Regex;
+
+/// The regex of a `#[validate(regex = "...")]` rule, compiled on first use. The derive already
+/// rejects invalid patterns, so compiling it cannot fail here.
+#[cfg(feature = "regex")]
+pub fn regex(cell: &'static std::sync::OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("pattern checked by the derive"))
+}
+
+pub use crate::collect::Collected;
+pub use crate::content::{Content, ContentDeserializer, ContentMap};
+use crate::{ErrorKind, FieldError, StructValidator};
+
+/// Error carrying `error` as the error of `key`, or under the `key` path if `error` carries the
+/// errors of a nested validated struct.
+pub fn nest_error<E, T>(key: &str, error: T) -> E
+where
+    E: de::Error,
+    T: Display,
+{
+    let mut errors = StructValidator::new();
+    errors.insert_error(key, error);
+    errors.into_de_error()
+}
+
+pub fn missing<E>(key: &str) -> E
+where
+    E: de::Error,
+{
+    StructValidator::new()
+        .with_field_error(key, FieldError::missing())
+        .into_de_error()
+}
+
+pub fn unknown_variant<E>(key: &str, variant: &str, variants: &[&str]) -> E
+where
+    E: de::Error,
+{
+    StructValidator::new()
+        .with_field_error(key, FieldError::unknown_variant(variant, variants))
+        .into_de_error()
+}
+
+/// Removes the tag of an internally or adjacently tagged enum from the buffered object.
+pub fn take_tag<E>(object: &mut ContentMap, tag: &str) -> Result<String, E>
+where
+    E: de::Error,
+{
+    match object.remove(tag) {
+        Some(value) => de::Deserialize::deserialize(ContentDeserializer::<E>::new(value))
+            .map_err(|e| nest_error(tag, e)),
+        None => Err(missing(tag)),
+    }
+}
+
+/// Runs `f`, returning the warnings of the values it deserialized instead of logging them.
+pub fn collect_warnings<T, F>(f: F) -> (T, StructValidator)
+where
+    F: FnOnce() -> T,
+{
+    let outer = crate::WARNINGS.with(|warnings| warnings.replace(Some(StructValidator::new())));
+    let value = f();
+    let collected = crate::WARNINGS.with(|warnings| warnings.replace(outer));
+    (value, collected.unwrap_or_default())
+}
+
+/// Hands the warnings of a successfully deserialized value to `deserialize_with_warnings`, or
+/// logs them outside of it.
+pub fn emit_warnings(errors: StructValidator) {
+    let unclaimed = crate::WARNINGS.with(|warnings| match warnings.borrow_mut().as_mut() {
+        Some(collected) => {
+            collected.merge(StructValidator {
+                errors: Default::default(),
+                warnings: errors.warnings,
+            });
+            None
+        }
+        None => Some(errors),
+    });
+    if let Some(errors) = unclaimed {
+        errors.log_warnings();
+    }
+}
+
+/// Runs `deserialize`, moving the warnings emitted by the value of `key` it deserializes into
+/// `errors`, under `key`, so they follow the errors of the enclosing value. Outside of
+/// `deserialize_with_warnings` too, so that the outermost value logs them with their whole path.
+pub fn nest_warnings<K, T, F>(errors: &mut StructValidator, key: K, deserialize: F) -> T
+where
+    K: Into<String>,
+    F: FnOnce() -> T,
+{
+    let (value, nested) = collect_warnings(deserialize);
+    errors.extend_nested(key, nested);
+    value
+}
+
+/// [`nest_warnings`] for the content of an enum variant, emitting the warnings under `key`.
+pub fn nest_variant_warnings<T, F>(key: &str, deserialize: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let mut errors = StructValidator::new();
+    let value = nest_warnings(&mut errors, key, deserialize);
+    emit_warnings(errors);
+    value
+}
+
+/// Error for an untagged enum none of whose variants matched, given the errors of each variant
+/// recovered with `StructValidator::from_de_error`. Reports the variant that came closest: the
+/// one missing the fewest fields, then the one with the fewest errors.
+pub fn untagged_error<E>(name: &str, attempts: Vec<Option<StructValidator>>) -> E
+where
+    E: de::Error,
+{
+    let mut errors = attempts
+        .into_iter()
+        .flatten()
+        .min_by_key(|errors| {
+            let errors = errors.errors.values().flatten();
+            let missing = errors
+                .clone()
+                .filter(|e| e.kind == ErrorKind::Missing)
+                .count();
+            (missing, errors.count())
+        })
+        .unwrap_or_default();
+    errors.insert_field_error(
+        StructValidator::ROOT,
+        FieldError::custom(format!(
+            "data did not match any variant of untagged enum {}",
+            name
+        )),
+    );
+    errors.into_de_error()
+}
@@ -0,0 +1,264 @@
+//! Checks behind the `#[validate(...)]` field rules of `#[derive(Validate)]`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Display};
+
+use crate::{ErrorKind, FieldError};
+
+/// Values whose length can be checked with [`length`]. Strings count characters, not bytes.
+pub trait HasLength {
+    fn length(&self) -> usize;
+}
+
+impl HasLength for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl HasLength for String {
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl<T> HasLength for &T
+where
+    T: HasLength + ?Sized,
+{
+    fn length(&self) -> usize {
+        (**self).length()
+    }
+}
+
+impl<T> HasLength for [T] {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, const N: usize> HasLength for [T; N] {
+    fn length(&self) -> usize {
+        N
+    }
+}
+
+macro_rules! has_length {
+    ($($ty:ident<$($param:ident),*>),*) => {
+        $(
+            impl<$($param),*> HasLength for $ty<$($param),*> {
+                fn length(&self) -> usize {
+                    self.len()
+                }
+            }
+        )*
+    };
+}
+
+has_length!(
+    Vec<T>,
+    VecDeque<T>,
+    HashSet<T, S>,
+    BTreeSet<T>,
+    HashMap<K, V, S>,
+    BTreeMap<K, V>
+);
+
+pub fn range<T>(value: &T, min: Option<T>, max: Option<T>) -> Result<(), FieldError>
+where
+    T: PartialOrd + Display,
+{
+    let below = min.as_ref().is_some_and(|min| value < min);
+    let above = max.as_ref().is_some_and(|max| value > max);
+    if !below && !above {
+        return Ok(());
+    }
+    let expected = match (min, max) {
+        (Some(min), Some(max)) => format!("a value between {} and {}", min, max),
+        (Some(min), None) => format!("a value of at least {}", min),
+        (None, Some(max)) => format!("a value of at most {}", max),
+        (None, None) => unreachable!(),
+    };
+    Err(FieldError::invalid_value(
+        &format!("`{}`", value),
+        &expected,
+    ))
+}
+
+pub fn length<T>(
+    value: &T,
+    min: Option<usize>,
+    max: Option<usize>,
+    equal: Option<usize>,
+) -> Result<(), FieldError>
+where
+    T: HasLength + ?Sized,
+{
+    let len = value.length();
+    let expected = match (equal, min, max) {
+        (Some(equal), _, _) if len != equal => format!("a length of {}", equal),
+        (None, Some(min), Some(max)) if len < min || len > max => {
+            format!("a length between {} and {}", min, max)
+        }
+        (None, Some(min), None) if len < min => format!("a length of at least {}", min),
+        (None, None, Some(max)) if len > max => format!("a length of at most {}", max),
+        _ => return Ok(()),
+    };
+    Err(FieldError::invalid_length(len, &expected))
+}
+
+/// Accepts `local@domain`, where the domain has at least two non-empty labels.
+pub fn email(value: &str) -> Result<(), FieldError> {
+    let valid = match value.rsplit_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !value.contains(char::is_whitespace)
+                && domain.contains('.')
+                && domain.split('.').all(|label| !label.is_empty())
+        }
+        None => false,
+    };
+    check_str(valid, value, "an email address")
+}
+
+/// Accepts absolute URLs, `scheme://host...`.
+pub fn url(value: &str) -> Result<(), FieldError> {
+    let valid = match value.split_once("://") {
+        Some((scheme, rest)) => {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                && !rest.starts_with(['/', '?', '#'])
+                && !rest.is_empty()
+                && !value.contains(char::is_whitespace)
+        }
+        None => false,
+    };
+    check_str(valid, value, "a URL")
+}
+
+/// Fails if the field isn't `present` while `field` has one of the given `values` (`equals`).
+pub fn required_if<V>(
+    present: bool,
+    equals: bool,
+    field: &str,
+    values: &[&V],
+) -> Result<(), FieldError>
+where
+    V: Debug + ?Sized,
+{
+    if present || !equals {
+        Ok(())
+    } else {
+        Err(required(format!("when `{}` is {}", field, one_of(values))))
+    }
+}
+
+/// Fails if the field isn't `present` while `field` has none of the given `values` (`equals`).
+pub fn required_unless<V>(
+    present: bool,
+    equals: bool,
+    field: &str,
+    values: &[&V],
+) -> Result<(), FieldError>
+where
+    V: Debug + ?Sized,
+{
+    if present || equals {
+        Ok(())
+    } else {
+        Err(required(format!(
+            "unless `{}` is {}",
+            field,
+            one_of(values)
+        )))
+    }
+}
+
+/// `"a"` for a single value, `one of ["a", "b"]` otherwise.
+fn one_of<V>(values: &[&V]) -> String
+where
+    V: Debug + ?Sized,
+{
+    match values {
+        [value] => format!("{:?}", value),
+        values => format!("one of {:?}", values),
+    }
+}
+
+/// Fails if the field isn't `present` while any of the `fields` is, given as `(name, present)`.
+pub fn required_with(present: bool, fields: &[(&str, bool)]) -> Result<(), FieldError> {
+    match fields.iter().find(|(_, other)| *other) {
+        Some((field, _)) if !present => Err(required(format!("when `{}` is present", field))),
+        _ => Ok(()),
+    }
+}
+
+/// Fails unless exactly one of the `fields`, given as `(name, present)`, is present.
+pub fn exactly_one_of(fields: &[(&str, bool)]) -> Result<(), FieldError> {
+    at_least_one_of(fields).and_then(|()| at_most_one_of(fields))
+}
+
+/// Fails if more than one of the `fields`, given as `(name, present)`, is present.
+pub fn at_most_one_of(fields: &[(&str, bool)]) -> Result<(), FieldError> {
+    let present: Vec<_> = fields.iter().filter(|(_, present)| *present).collect();
+    match present.len() {
+        0 | 1 => Ok(()),
+        _ => Err(FieldError::new(
+            ErrorKind::Conflict,
+            format!("only one of {} may be set", names(fields)),
+        )
+        .with_expected(format!("at most one of {}", names(fields)))
+        .with_actual(names(present))),
+    }
+}
+
+/// Fails if none of the `fields`, given as `(name, present)`, is present.
+pub fn at_least_one_of(fields: &[(&str, bool)]) -> Result<(), FieldError> {
+    if fields.iter().any(|(_, present)| *present) {
+        Ok(())
+    } else {
+        Err(FieldError::new(
+            ErrorKind::Missing,
+            format!("one of {} is required", names(fields)),
+        )
+        .with_expected(format!("at least one of {}", names(fields))))
+    }
+}
+
+fn names<'a, I>(fields: I) -> String
+where
+    I: IntoIterator<Item = &'a (&'a str, bool)>,
+{
+    fields
+        .into_iter()
+        .map(|(name, _)| format!("`{}`", name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn required(condition: String) -> FieldError {
+    FieldError::new(
+        ErrorKind::Missing,
+        format!("field is missing, it is required {}", condition),
+    )
+    .with_expected(format!("a value {}", condition))
+}
+
+#[cfg(feature = "regex")]
+pub fn regex(value: &str, regex: &regex::Regex) -> Result<(), FieldError> {
+    let expected = format!("a string matching `{}`", regex);
+    check_str(regex.is_match(value), value, &expected)
+}
+
+fn check_str(valid: bool, value: &str, expected: &str) -> Result<(), FieldError> {
+    if valid {
+        Ok(())
+    } else {
+        Err(FieldError::invalid_value(
+            &format!("string {:?}", value),
+            expected,
+        ))
+    }
+}
@@ -0,0 +1,617 @@
+use proc_macro2::TokenTree;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{
+    bracketed, token, Attribute, Data, DeriveInput, Expr, ExprPath, Ident, LitStr, Token, Type,
+};
+
+use crate::case::RenameRule;
+
+#[allow(clippy::enum_variant_names)]
+pub enum Default {
+    None,
+    Default,
+    Path(ExprPath),
+}
+
+pub enum UnknownFields {
+    Ignore,
+    Reject,
+    Warn,
+}
+
+pub enum Tagging {
+    External,
+    Internal { tag: String },
+    Adjacent { tag: String, content: String },
+    Untagged,
+}
+
+pub struct Container {
+    pub name: String,
+    pub default: Default,
+    pub rename_all: RenameRule,
+    pub unknown_fields: UnknownFields,
+    pub tagging: Tagging,
+    pub transparent: bool,
+    pub partial: bool,
+    pub rules: Vec<StructRule>,
+    /// Type of the context received by the `custom_with`, `schema_with` and async rules.
+    pub context: Option<Type>,
+}
+
+/// A `#[validate(...)]` rule of a whole struct, run once its fields are parsed.
+pub enum StructRule {
+    /// `fn(&Self) -> Result<(), E>` with `E: Into<FieldError>`, reported under the root key.
+    Custom(ExprPath),
+    /// `fn(&Self) -> Result<(), StructValidator>`, skipped if any of `fields` already failed.
+    Schema {
+        function: ExprPath,
+        fields: Vec<Ident>,
+    },
+    /// `fn(&Self, &Ctx) -> Result<(), StructValidator>`, run by `ValidateWith`.
+    SchemaWith(ExprPath),
+    /// `async fn(&Self, &Ctx) -> Result<(), StructValidator>`, run by `ValidateAsync`.
+    AsyncSchema(ExprPath),
+    /// How many of `fields` may be set, reported on each of them.
+    Group { kind: Group, fields: Vec<Ident> },
+}
+
+#[allow(clippy::enum_variant_names)]
+pub enum Group {
+    ExactlyOne,
+    AtMostOne,
+    AtLeastOne,
+}
+
+pub struct Variant {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub rename_all: RenameRule,
+    pub skip_deserializing: bool,
+}
+
+pub struct Field {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub default: Default,
+    pub skip_deserializing: bool,
+    pub rules: Vec<Rule>,
+}
+
+/// A `#[validate(...)]` rule of a field.
+pub enum Rule {
+    Range {
+        min: Option<Expr>,
+        max: Option<Expr>,
+    },
+    Length {
+        min: Option<Expr>,
+        max: Option<Expr>,
+        equal: Option<Expr>,
+    },
+    Regex(LitStr),
+    Email,
+    Url,
+    Custom(ExprPath),
+    /// `fn(&T, &Ctx) -> Result<(), E>`, run by `ValidateWith`.
+    CustomWith(ExprPath),
+    /// `async fn(&T, &Ctx) -> Result<(), E>`, run by `ValidateAsync`.
+    AsyncCustom(ExprPath),
+    Nested,
+    /// Conditional requirements of an `Option` field, checked once all the fields are parsed.
+    RequiredIf {
+        field: Ident,
+        values: Vec<Expr>,
+    },
+    RequiredUnless {
+        field: Ident,
+        values: Vec<Expr>,
+    },
+    RequiredWith(Vec<Ident>),
+}
+
+impl Container {
+    pub fn from_attrs(ident: &Ident, attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut container = Container {
+            name: ident.unraw().to_string(),
+            default: Default::None,
+            rename_all: RenameRule::None,
+            unknown_fields: UnknownFields::Ignore,
+            tagging: Tagging::External,
+            transparent: false,
+            partial: false,
+            rules: Vec::new(),
+            context: None,
+        };
+        let mut tag = None;
+        let mut content = None;
+        let mut untagged = None;
+        for_each_serde_meta(attrs, |meta| {
+            if meta.path.is_ident("default") {
+                container.default = parse_default(&meta)?;
+            } else if meta.path.is_ident("rename") {
+                if let Some(name) = parse_deserialize_name(&meta)? {
+                    container.name = name.value();
+                }
+            } else if meta.path.is_ident("rename_all") {
+                if let Some(rule) = parse_deserialize_name(&meta)? {
+                    container.rename_all = RenameRule::from_str(&rule.value())
+                        .map_err(|e| syn::Error::new(rule.span(), e))?;
+                }
+            } else if meta.path.is_ident("deny_unknown_fields") {
+                container.unknown_fields = UnknownFields::Reject;
+            } else if meta.path.is_ident("tag") {
+                tag = Some(parse_lit_str(&meta)?);
+            } else if meta.path.is_ident("content") {
+                content = Some(parse_lit_str(&meta)?);
+            } else if meta.path.is_ident("untagged") {
+                untagged = Some(meta.path.clone());
+            } else if meta.path.is_ident("transparent") {
+                container.transparent = true;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+        for_each_validate_meta(attrs, |meta| {
+            if meta.path.is_ident("unknown_fields") {
+                let policy = parse_lit_str(&meta)?;
+                container.unknown_fields = match policy.value().as_str() {
+                    "ignore" => UnknownFields::Ignore,
+                    "reject" => UnknownFields::Reject,
+                    "warn" => UnknownFields::Warn,
+                    _ => {
+                        return Err(syn::Error::new(
+                            policy.span(),
+                            "expected one of \"ignore\", \"reject\", \"warn\"",
+                        ))
+                    }
+                };
+                Ok(())
+            } else if meta.path.is_ident("partial") {
+                container.partial = true;
+                Ok(())
+            } else if meta.path.is_ident("custom") {
+                let function = parse_lit_str(&meta)?.parse()?;
+                container.rules.push(StructRule::Custom(function));
+                Ok(())
+            } else if meta.path.is_ident("schema") {
+                container.rules.push(parse_schema(&meta)?);
+                Ok(())
+            } else if meta.path.is_ident("schema_with") {
+                let function = parse_lit_str(&meta)?.parse()?;
+                container.rules.push(StructRule::SchemaWith(function));
+                Ok(())
+            } else if meta.path.is_ident("async_schema") {
+                require_feature(&meta, cfg!(feature = "async"), "async")?;
+                let function = parse_lit_str(&meta)?.parse()?;
+                container.rules.push(StructRule::AsyncSchema(function));
+                Ok(())
+            } else if meta.path.is_ident("context") {
+                container.context = Some(parse_lit_str(&meta)?.parse()?);
+                Ok(())
+            } else if let Some(kind) = parse_group_kind(&meta) {
+                let mut fields = Vec::new();
+                meta.parse_nested_meta(|meta| {
+                    fields.push(meta.path.require_ident()?.clone());
+                    Ok(())
+                })?;
+                if fields.len() < 2 {
+                    return Err(meta.error("a field group requires at least two fields"));
+                }
+                container.rules.push(StructRule::Group { kind, fields });
+                Ok(())
+            } else {
+                Err(meta.error("unsupported validate attribute"))
+            }
+        })?;
+        container.tagging = match (tag, content, untagged) {
+            (None, None, None) => Tagging::External,
+            (Some(tag), None, None) => Tagging::Internal { tag: tag.value() },
+            (Some(tag), Some(content), None) => Tagging::Adjacent {
+                tag: tag.value(),
+                content: content.value(),
+            },
+            (None, None, Some(_)) => Tagging::Untagged,
+            (None, Some(content), _) => {
+                return Err(syn::Error::new(
+                    content.span(),
+                    "`content` requires `tag` to be set too",
+                ))
+            }
+            (_, _, Some(untagged)) => {
+                return Err(syn::Error::new_spanned(
+                    untagged,
+                    "`untagged` cannot be combined with `tag` or `content`",
+                ))
+            }
+        };
+        Ok(container)
+    }
+}
+
+impl Variant {
+    pub fn from_attrs(
+        ident: &Ident,
+        attrs: &[Attribute],
+        container: &Container,
+    ) -> syn::Result<Self> {
+        let mut variant = Variant {
+            name: container
+                .rename_all
+                .apply_to_variant(&ident.unraw().to_string()),
+            aliases: Vec::new(),
+            rename_all: RenameRule::None,
+            skip_deserializing: false,
+        };
+        for_each_serde_meta(attrs, |meta| {
+            if meta.path.is_ident("rename") {
+                if let Some(name) = parse_deserialize_name(&meta)? {
+                    variant.name = name.value();
+                }
+            } else if meta.path.is_ident("rename_all") {
+                if let Some(rule) = parse_deserialize_name(&meta)? {
+                    variant.rename_all = RenameRule::from_str(&rule.value())
+                        .map_err(|e| syn::Error::new(rule.span(), e))?;
+                }
+            } else if meta.path.is_ident("alias") {
+                variant.aliases.push(parse_lit_str(&meta)?.value());
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                variant.skip_deserializing = true;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+        Ok(variant)
+    }
+}
+
+impl Field {
+    /// `name` is the name of the field before any `rename`, its position for tuple fields.
+    pub fn from_attrs(name: String, attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut field = Field {
+            name,
+            aliases: Vec::new(),
+            default: Default::None,
+            skip_deserializing: false,
+            rules: Vec::new(),
+        };
+        for_each_serde_meta(attrs, |meta| {
+            if meta.path.is_ident("default") {
+                field.default = parse_default(&meta)?;
+            } else if meta.path.is_ident("rename") {
+                if let Some(name) = parse_deserialize_name(&meta)? {
+                    field.name = name.value();
+                }
+            } else if meta.path.is_ident("alias") {
+                field.aliases.push(parse_lit_str(&meta)?.value());
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                field.skip_deserializing = true;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+        for_each_validate_meta(attrs, |meta| {
+            field.rules.push(parse_rule(&meta)?);
+            Ok(())
+        })?;
+        Ok(field)
+    }
+}
+
+fn parse_group_kind(meta: &ParseNestedMeta) -> Option<Group> {
+    if meta.path.is_ident("exactly_one_of") {
+        Some(Group::ExactlyOne)
+    } else if meta.path.is_ident("at_most_one_of") {
+        Some(Group::AtMostOne)
+    } else if meta.path.is_ident("at_least_one_of") {
+        Some(Group::AtLeastOne)
+    } else {
+        None
+    }
+}
+
+/// Parses both `schema = "function"` and `schema(function = "...", fields(a, b))`.
+fn parse_schema(meta: &ParseNestedMeta) -> syn::Result<StructRule> {
+    if meta.input.peek(Token![=]) {
+        return Ok(StructRule::Schema {
+            function: parse_lit_str(meta)?.parse()?,
+            fields: Vec::new(),
+        });
+    }
+    let mut function = None;
+    let mut fields = Vec::new();
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("function") {
+            function = Some(parse_lit_str(&meta)?.parse()?);
+        } else if meta.path.is_ident("fields") {
+            meta.parse_nested_meta(|meta| {
+                fields.push(meta.path.require_ident()?.clone());
+                Ok(())
+            })?;
+        } else {
+            return Err(meta.error("expected `function` or `fields`"));
+        }
+        Ok(())
+    })?;
+    match function {
+        Some(function) => Ok(StructRule::Schema { function, fields }),
+        None => Err(meta.error("`schema` requires a `function`")),
+    }
+}
+
+/// Parses the `(field = other, value = expr)` of `required_if` and `required_unless`, where the
+/// value can also be a list of values, `value = [expr, ..]`, any of which meets the condition.
+fn parse_condition(meta: &ParseNestedMeta) -> syn::Result<(Ident, Vec<Expr>)> {
+    let (mut field, mut value) = (None, None);
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("field") {
+            field = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("value") {
+            let input = meta.value()?;
+            value = Some(if input.peek(token::Bracket) {
+                let content;
+                let brackets = bracketed!(content in input);
+                let values = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+                if values.is_empty() {
+                    return Err(syn::Error::new(
+                        brackets.span.join(),
+                        "expected at least one value",
+                    ));
+                }
+                values.into_iter().collect()
+            } else {
+                vec![input.parse()?]
+            });
+        } else {
+            return Err(meta.error("expected `field` or `value`"));
+        }
+        Ok(())
+    })?;
+    match (field, value) {
+        (Some(field), Some(values)) => Ok((field, values)),
+        _ => Err(meta.error("expected both `field` and `value`")),
+    }
+}
+
+fn parse_rule(meta: &ParseNestedMeta) -> syn::Result<Rule> {
+    if meta.path.is_ident("range") {
+        let (mut min, mut max) = (None, None);
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("min") {
+                min = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("max") {
+                max = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `min` or `max`"));
+            }
+            Ok(())
+        })?;
+        if min.is_none() && max.is_none() {
+            return Err(meta.error("`range` requires `min`, `max` or both"));
+        }
+        Ok(Rule::Range { min, max })
+    } else if meta.path.is_ident("length") {
+        let (mut min, mut max, mut equal) = (None, None, None);
+        meta.parse_nested_meta(|meta| {
+            if meta.path.is_ident("min") {
+                min = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("max") {
+                max = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("equal") {
+                equal = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `min`, `max` or `equal`"));
+            }
+            Ok(())
+        })?;
+        match (&min, &max, &equal) {
+            (None, None, None) => Err(meta.error("`length` requires `min`, `max` or `equal`")),
+            (Some(_), _, Some(_)) | (_, Some(_), Some(_)) => {
+                Err(meta.error("`equal` cannot be combined with `min` or `max`"))
+            }
+            _ => Ok(Rule::Length { min, max, equal }),
+        }
+    } else if meta.path.is_ident("regex") {
+        require_feature(meta, cfg!(feature = "regex"), "regex")?;
+        let pattern = parse_lit_str(meta)?;
+        check_pattern(&pattern)?;
+        Ok(Rule::Regex(pattern))
+    } else if meta.path.is_ident("email") {
+        Ok(Rule::Email)
+    } else if meta.path.is_ident("url") {
+        Ok(Rule::Url)
+    } else if meta.path.is_ident("custom") {
+        Ok(Rule::Custom(parse_lit_str(meta)?.parse()?))
+    } else if meta.path.is_ident("custom_with") {
+        Ok(Rule::CustomWith(parse_lit_str(meta)?.parse()?))
+    } else if meta.path.is_ident("async_custom") {
+        require_feature(meta, cfg!(feature = "async"), "async")?;
+        Ok(Rule::AsyncCustom(parse_lit_str(meta)?.parse()?))
+    } else if meta.path.is_ident("nested") {
+        Ok(Rule::Nested)
+    } else if meta.path.is_ident("required_if") {
+        let (field, values) = parse_condition(meta)?;
+        Ok(Rule::RequiredIf { field, values })
+    } else if meta.path.is_ident("required_unless") {
+        let (field, values) = parse_condition(meta)?;
+        Ok(Rule::RequiredUnless { field, values })
+    } else if meta.path.is_ident("required_with") {
+        let mut fields = Vec::new();
+        meta.parse_nested_meta(|meta| {
+            fields.push(meta.path.require_ident()?.clone());
+            Ok(())
+        })?;
+        if fields.is_empty() {
+            return Err(meta.error("`required_with` requires at least one field"));
+        }
+        Ok(Rule::RequiredWith(fields))
+    } else {
+        Err(meta.error("unsupported validate attribute"))
+    }
+}
+
+/// Serde attributes `ValidatedDeserialize` implements, or that only affect `Serialize`, for
+/// containers, variants and fields.
+const CONTAINER_ATTRS: &[&str] = &[
+    "default",
+    "rename",
+    "rename_all",
+    "deny_unknown_fields",
+    "tag",
+    "content",
+    "untagged",
+    "transparent",
+    "into",
+];
+const VARIANT_ATTRS: &[&str] = &[
+    "rename",
+    "rename_all",
+    "alias",
+    "skip",
+    "skip_deserializing",
+    "serialize_with",
+    "skip_serializing",
+];
+const FIELD_ATTRS: &[&str] = &[
+    "default",
+    "rename",
+    "alias",
+    "skip",
+    "skip_deserializing",
+    "serialize_with",
+    "skip_serializing",
+    "skip_serializing_if",
+    "getter",
+];
+
+/// Rejects the serde attributes `ValidatedDeserialize` doesn't implement (e.g. `flatten` or
+/// `deserialize_with`), which would otherwise change nothing. `Validate` only reads the names and
+/// ignores the other attributes.
+pub fn check_serde_attrs(input: &DeriveInput) -> syn::Result<()> {
+    check_serde_meta(&input.attrs, CONTAINER_ATTRS)?;
+    let variants: Vec<_> = match &input.data {
+        Data::Struct(data) => vec![(&[][..], &data.fields)],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|v| (&v.attrs[..], &v.fields))
+            .collect(),
+        Data::Union(_) => Vec::new(),
+    };
+    for (attrs, fields) in variants {
+        check_serde_meta(attrs, VARIANT_ATTRS)?;
+        for field in fields {
+            check_serde_meta(&field.attrs, FIELD_ATTRS)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_serde_meta(attrs: &[Attribute], supported: &[&str]) -> syn::Result<()> {
+    for_each_serde_meta(attrs, |meta| {
+        if supported.iter().any(|name| meta.path.is_ident(name)) {
+            skip_meta_value(&meta)
+        } else {
+            Err(meta.error("this serde attribute is not supported by ValidatedDeserialize"))
+        }
+    })
+}
+
+/// Error for rules whose runtime support is behind a disabled feature of struct-validator.
+fn require_feature(meta: &ParseNestedMeta, enabled: bool, feature: &str) -> syn::Result<()> {
+    if enabled {
+        Ok(())
+    } else {
+        Err(meta.error(format!(
+            "this rule requires the `{}` feature of struct-validator",
+            feature
+        )))
+    }
+}
+
+/// Compiles the pattern of a `regex` rule, so that an invalid one fails the build rather than the
+/// first validation using it.
+#[cfg(feature = "regex")]
+fn check_pattern(pattern: &LitStr) -> syn::Result<()> {
+    match regex::Regex::new(&pattern.value()) {
+        Ok(_) => Ok(()),
+        Err(err) => Err(syn::Error::new(pattern.span(), err)),
+    }
+}
+
+#[cfg(not(feature = "regex"))]
+fn check_pattern(_: &LitStr) -> syn::Result<()> {
+    Ok(())
+}
+
+fn for_each_serde_meta<F>(attrs: &[Attribute], f: F) -> syn::Result<()>
+where
+    F: FnMut(ParseNestedMeta) -> syn::Result<()>,
+{
+    for_each_meta(attrs, "serde", f)
+}
+
+fn for_each_validate_meta<F>(attrs: &[Attribute], f: F) -> syn::Result<()>
+where
+    F: FnMut(ParseNestedMeta) -> syn::Result<()>,
+{
+    for_each_meta(attrs, "validate", f)
+}
+
+fn for_each_meta<F>(attrs: &[Attribute], name: &str, mut f: F) -> syn::Result<()>
+where
+    F: FnMut(ParseNestedMeta) -> syn::Result<()>,
+{
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident(name)) {
+        attr.parse_nested_meta(&mut f)?;
+    }
+    Ok(())
+}
+
+fn parse_default(meta: &ParseNestedMeta) -> syn::Result<Default> {
+    if meta.input.peek(Token![=]) {
+        Ok(Default::Path(parse_lit_str(meta)?.parse()?))
+    } else {
+        Ok(Default::Default)
+    }
+}
+
+/// Parses both `name = "..."` and `name(serialize = "...", deserialize = "...")`, returning
+/// the value that applies when deserializing.
+fn parse_deserialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<LitStr>> {
+    if meta.input.peek(Token![=]) {
+        return parse_lit_str(meta).map(Some);
+    }
+    let mut name = None;
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("deserialize") {
+            name = Some(parse_lit_str(&meta)?);
+        } else if meta.path.is_ident("serialize") {
+            parse_lit_str(&meta)?;
+        } else {
+            return Err(meta.error("expected `serialize` or `deserialize`"));
+        }
+        Ok(())
+    })?;
+    Ok(name)
+}
+
+fn parse_lit_str(meta: &ParseNestedMeta) -> syn::Result<LitStr> {
+    meta.value()?.parse()
+}
+
+/// Serde attributes the derives don't act on (e.g. the ones meant for `Serialize`) are skipped
+/// when parsing, `check_serde_attrs` rejects the unsupported ones.
+fn skip_meta_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<TokenTree>()?;
+    } else if !meta.input.is_empty() && !meta.input.peek(Token![,]) {
+        meta.input.parse::<TokenTree>()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,1474 @@
+use std::iter;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{
+    parse_quote, Data, DataEnum, DataStruct, DeriveInput, Fields, GenericArgument, GenericParam,
+    Generics, Ident, Index, LifetimeParam, LitByteStr, Member, PathArguments, Type, Visibility,
+};
+
+use crate::attr;
+use crate::case::RenameRule;
+use crate::validate;
+
+pub struct Field<'a> {
+    pub member: Member,
+    vis: &'a Visibility,
+    pub ty: &'a Type,
+    variant: Ident,
+    local: Ident,
+    pub attrs: attr::Field,
+}
+
+impl Field<'_> {
+    /// Seed deserializing the value of the field.
+    fn seed(&self) -> TokenStream {
+        let ty = self.ty;
+        if is_collection(ty) {
+            quote!(::struct_validator::__private::Collected::<#ty>(::std::marker::PhantomData))
+        } else {
+            quote!(::std::marker::PhantomData::<#ty>)
+        }
+    }
+
+    /// Statements checking the freshly deserialized `__value` against the rules of the field,
+    /// recording the errors in `__invalid` under the name of the field. `nested` rules run
+    /// `Validate` again on values that were already checked if their type derives
+    /// `ValidatedDeserialize`, but also cover types that only implement `Deserialize`.
+    fn check(&self) -> TokenStream {
+        let name = &self.attrs.name;
+        self.check_under(quote!(#name))
+    }
+
+    /// [`Field::check`], recording the errors under `name`.
+    fn check_under(&self, name: TokenStream) -> TokenStream {
+        validate::check_rules(
+            quote!(__invalid),
+            quote!(&__value),
+            self.ty,
+            name,
+            &self.attrs.rules,
+        )
+    }
+
+    /// Value used when the field is absent from the input, `None` if the field is required.
+    fn fallback(&self, default: &attr::Default) -> Option<TokenStream> {
+        let member = &self.member;
+        match &self.attrs.default {
+            attr::Default::Default => Some(quote!(::std::default::Default::default())),
+            attr::Default::Path(path) => Some(quote!(#path())),
+            attr::Default::None => match default {
+                attr::Default::None if is_option(self.ty) => {
+                    Some(quote!(::std::option::Option::None))
+                }
+                attr::Default::None if self.attrs.skip_deserializing => {
+                    Some(quote!(::std::default::Default::default()))
+                }
+                attr::Default::None => None,
+                _ => Some(quote!(__default.#member)),
+            },
+        }
+    }
+}
+
+struct Variant<'a> {
+    ident: &'a Ident,
+    fields: &'a Fields,
+    tag: Ident,
+    attrs: attr::Variant,
+}
+
+/// What the visitor of a struct or struct variant builds.
+enum Output<'a> {
+    /// `construct { .. }`, failing with the errors of the fields if any.
+    Value(TokenStream),
+    /// The given `{Name}Partial` struct, along with the errors of the fields.
+    Partial(&'a Ident),
+}
+
+/// The type being derived for, shared by the visitors generated for it.
+struct Params<'a> {
+    name: &'a Ident,
+    generics: &'a Generics,
+    de_generics: Generics,
+    /// Struct-level rules, run once the value is built.
+    rules: &'a [attr::StructRule],
+}
+
+/// Generics of the `Deserialize` impl: the struct's own plus `'de`, which outlives every borrowed
+/// lifetime, and a `Deserialize<'de>` bound on every type parameter.
+fn de_generics(generics: &Generics) -> Generics {
+    let mut de_generics = generics.clone();
+    for param in de_generics.type_params_mut() {
+        param.bounds.push(parse_quote!(serde::Deserialize<'de>));
+    }
+    let lifetimes = generics.lifetimes().map(|param| &param.lifetime);
+    let de_lifetime: LifetimeParam = parse_quote!('de: #(#lifetimes)+*);
+    de_generics
+        .params
+        .insert(0, GenericParam::Lifetime(de_lifetime));
+    de_generics
+}
+
+/// Whether these are the fields of a tuple struct or variant with more than one field.
+fn is_tuple(fields: &Fields) -> bool {
+    matches!(fields, Fields::Unnamed(fields) if fields.unnamed.len() > 1)
+}
+
+pub fn is_option(ty: &Type) -> bool {
+    matches!(type_name(ty), Some((name, 1)) if name == "Option")
+}
+
+/// Collections deserialized element by element, see `__private::Collected`, also when optional.
+/// They are recognized by name, so collections behind a type alias are deserialized as a whole.
+fn is_collection(ty: &Type) -> bool {
+    match type_name(ty) {
+        _ if matches!(ty, Type::Array(_)) => true,
+        Some((name, 1)) if name == "Option" => type_argument(ty).is_some_and(is_collection),
+        Some((name, 1)) if name == "Box" => matches!(type_argument(ty), Some(Type::Slice(_))),
+        Some((name, 1)) => {
+            name == "Vec" || name == "VecDeque" || name == "HashSet" || name == "BTreeSet"
+        }
+        Some((name, 2)) => name == "HashMap" || name == "BTreeMap" || name == "HashSet",
+        Some((name, 3)) => name == "HashMap",
+        _ => false,
+    }
+}
+
+/// First type argument of a path type, e.g. `T` for `Option<T>`.
+fn type_argument(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(ty) => ty.path.segments.last()?,
+        _ => return None,
+    };
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Last segment of a path type and its number of type arguments, e.g. `Vec` and 1 for
+/// `std::vec::Vec<T>`.
+fn type_name(ty: &Type) -> Option<(&Ident, usize)> {
+    let segment = match ty {
+        Type::Path(ty) if ty.qself.is_none() => ty.path.segments.last()?,
+        _ => return None,
+    };
+    let args = match &segment.arguments {
+        PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .filter(|arg| matches!(arg, GenericArgument::Type(_)))
+            .count(),
+        _ => 0,
+    };
+    Some((&segment.ident, args))
+}
+
+/// Fields of a struct or variant, tuple fields being named after their position.
+pub fn struct_fields(fields: &Fields, rename_all: RenameRule) -> syn::Result<Vec<Field<'_>>> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let (member, name) = match &field.ident {
+                Some(ident) => (
+                    Member::Named(ident.clone()),
+                    rename_all.apply_to_field(&ident.unraw().to_string()),
+                ),
+                None => (Member::Unnamed(Index::from(i)), i.to_string()),
+            };
+            Ok(Field {
+                member,
+                vis: &field.vis,
+                ty: &field.ty,
+                variant: format_ident!("__field{}", i),
+                local: format_ident!("__field{}", i),
+                attrs: attr::Field::from_attrs(name, &field.attrs)?,
+            })
+        })
+        .collect()
+}
+
+pub fn expand_derive_deserialize(input: &DeriveInput) -> syn::Result<TokenStream> {
+    attr::check_serde_attrs(input)?;
+    let container = attr::Container::from_attrs(&input.ident, &input.attrs)?;
+    let params = Params {
+        name: &input.ident,
+        generics: &input.generics,
+        de_generics: de_generics(&input.generics),
+        rules: &container.rules,
+    };
+    let name = params.name;
+    let name_str = &container.name;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = struct_fields(&data.fields, container.rename_all)?;
+            match &data.fields {
+                _ if container.transparent => {
+                    deserialize_transparent(&params, &fields, &container.default)?
+                }
+                Fields::Named(_) => deserialize_fields(
+                    &params,
+                    &container,
+                    Output::Value(quote!(#name)),
+                    &fields,
+                    &container.default,
+                    name_str,
+                    |visitor| {
+                        quote! {
+                            serde::Deserializer::deserialize_struct(
+                                __deserializer,
+                                #name_str,
+                                FIELDS,
+                                #visitor,
+                            )
+                        }
+                    },
+                ),
+                Fields::Unnamed(_) if fields.len() == 1 => {
+                    deserialize_newtype(&params, &fields[0], name_str)
+                }
+                Fields::Unnamed(_) => {
+                    let len = fields.len();
+                    deserialize_tuple(
+                        &params,
+                        quote!(#name),
+                        &fields,
+                        &container.default,
+                        &format!("tuple struct {}", name_str),
+                        |visitor| {
+                            quote! {
+                                serde::Deserializer::deserialize_tuple_struct(
+                                    __deserializer,
+                                    #name_str,
+                                    #len,
+                                    #visitor,
+                                )
+                            }
+                        },
+                    )
+                }
+                Fields::Unit => {
+                    return Err(syn::Error::new(
+                        Span::call_site(),
+                        "ValidatedDeserialize does not support unit structs",
+                    ))
+                }
+            }
+        }
+        Data::Enum(_) if !container.rules.is_empty() => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "struct-level validate rules are only supported on structs",
+            ))
+        }
+        Data::Enum(data) => deserialize_enum(&params, &container, data)?,
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "ValidatedDeserialize only supports structs and enums",
+            ))
+        }
+    };
+
+    let (partial_struct, partial_impl) = if container.partial {
+        expand_partial(input, &params, &container)?
+    } else {
+        (quote!(), quote!())
+    };
+
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    let (de_impl_generics, _, de_where_clause) = params.de_generics.split_for_impl();
+    // `serde` paths resolve to the re-export, so users don't need serde as a direct dependency.
+    Ok(quote! {
+        #partial_struct
+
+        const _: () = {
+            use ::struct_validator::__private::serde;
+
+            impl #de_impl_generics serde::Deserialize<'de> for #name #ty_generics #de_where_clause {
+                fn deserialize<__D>(__deserializer: __D) -> ::std::result::Result<Self, __D::Error>
+                where
+                    __D: serde::Deserializer<'de>,
+                {
+                    #body
+                }
+            }
+
+            #partial_impl
+        };
+    })
+}
+
+/// The `{Name}Partial` struct of `#[validate(partial)]`, holding each field or its errors, and the
+/// `DeserializePartial` impl producing it.
+fn expand_partial(
+    input: &DeriveInput,
+    params: &Params,
+    container: &attr::Container,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let fields = match &input.data {
+        Data::Struct(
+            data @ DataStruct {
+                fields: Fields::Named(_),
+                ..
+            },
+        ) if !container.transparent => struct_fields(&data.fields, container.rename_all)?,
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "#[validate(partial)] only supports structs with named fields",
+            ))
+        }
+    };
+    let name = params.name;
+    let name_str = &container.name;
+    let vis = &input.vis;
+    let partial = format_ident!("{}Partial", name);
+    let (_, ty_generics, where_clause) = params.generics.split_for_impl();
+    let (de_impl_generics, _, de_where_clause) = params.de_generics.split_for_impl();
+    let generics = params.generics;
+    let members = fields.iter().map(|f| &f.member);
+    let field_vis = fields.iter().map(|f| f.vis);
+    let tys = fields.iter().map(|f| f.ty);
+    let doc = format!(
+        "[`{}`] with each field either deserialized or holding its errors.",
+        name
+    );
+    let body = deserialize_fields(
+        params,
+        container,
+        Output::Partial(&partial),
+        &fields,
+        &container.default,
+        name_str,
+        |visitor| {
+            quote! {
+                serde::Deserializer::deserialize_struct(
+                    __deserializer,
+                    #name_str,
+                    FIELDS,
+                    #visitor,
+                )
+            }
+        },
+    );
+
+    let partial_struct = quote! {
+        #[doc = #doc]
+        #vis struct #partial #generics #where_clause {
+            #(#field_vis #members: ::std::result::Result<#tys, ::struct_validator::StructValidator>,)*
+        }
+    };
+    let partial_impl = quote! {
+        impl #de_impl_generics ::struct_validator::DeserializePartial<'de> for #name #ty_generics #de_where_clause {
+            type Partial = #partial #ty_generics;
+
+            fn deserialize_partial<__D>(
+                __deserializer: __D,
+            ) -> ::std::result::Result<(Self::Partial, ::struct_validator::StructValidator), __D::Error>
+            where
+                __D: serde::Deserializer<'de>,
+            {
+                // Collecting makes the fields report the warnings of their values to `__errors`.
+                ::struct_validator::__private::collect_warnings(|| #body).0
+            }
+        }
+    };
+    Ok((partial_struct, partial_impl))
+}
+
+/// Block deserializing `fields` into `output`, collecting the errors of every field.
+/// `deserialize` receives the visitor and hands it to the deserializer, with `FIELDS` in scope.
+fn deserialize_fields(
+    params: &Params,
+    container: &attr::Container,
+    output: Output,
+    fields: &[Field],
+    default: &attr::Default,
+    subject: &str,
+    deserialize: impl FnOnce(TokenStream) -> TokenStream,
+) -> TokenStream {
+    let deserialized: Vec<_> = fields
+        .iter()
+        .filter(|f| !f.attrs.skip_deserializing)
+        .collect();
+
+    let name = params.name;
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    let (de_impl_generics, de_ty_generics, de_where_clause) = params.de_generics.split_for_impl();
+    let expecting = format!("object {}", subject);
+    let field_expecting = format!("a field of {}", subject);
+    let seq_expecting = format!("struct {} with {} elements", subject, deserialized.len());
+
+    let variants: Vec<_> = deserialized.iter().map(|f| &f.variant).collect();
+    let locals: Vec<_> = deserialized.iter().map(|f| &f.local).collect();
+    let seeds: Vec<_> = deserialized.iter().map(|f| f.seed()).collect();
+    let checks: Vec<_> = deserialized.iter().map(|f| f.check()).collect();
+    let names: Vec<_> = deserialized.iter().map(|f| f.attrs.name.as_str()).collect();
+    let tys: Vec<_> = deserialized.iter().map(|f| f.ty).collect();
+    let field_patterns = deserialized
+        .iter()
+        .map(|f| str_pattern(&f.attrs.name, &f.attrs.aliases));
+    let visit_identifier = visit_identifier(
+        quote!(__Field),
+        &variants,
+        deserialized
+            .iter()
+            .map(|f| bytes_pattern(&f.attrs.name, &f.attrs.aliases)),
+    );
+
+    let check_missing = deserialized
+        .iter()
+        .filter(|f| f.fallback(default).is_none())
+        .map(|f| {
+            let local = &f.local;
+            let name = &f.attrs.name;
+            quote! {
+                if #local.is_none() && !__errors.contains(#name) {
+                    __errors.insert_field_error(#name, ::struct_validator::FieldError::missing());
+                }
+            }
+        });
+    let (other_variant, other_value, other_arm) = match container.unknown_fields {
+        attr::UnknownFields::Ignore => (
+            quote!(__ignore),
+            quote!(__ignore),
+            quote! {
+                __Field::__ignore => {
+                    serde::de::MapAccess::next_value::<serde::de::IgnoredAny>(&mut __map)?;
+                }
+            },
+        ),
+        attr::UnknownFields::Reject | attr::UnknownFields::Warn => {
+            let record = match container.unknown_fields {
+                attr::UnknownFields::Warn => quote!(insert_warning),
+                _ => quote!(insert_field_error),
+            };
+            (
+                quote!(__other(::std::string::String)),
+                quote!(__other(::std::string::ToString::to_string(value))),
+                quote! {
+                    __Field::__other(__name) => {
+                        serde::de::MapAccess::next_value::<serde::de::IgnoredAny>(&mut __map)?;
+                        let __error = ::struct_validator::FieldError::unknown_field(&__name, FIELDS);
+                        __errors.#record(__name, __error);
+                    }
+                },
+            )
+        }
+    };
+    let visit_seq = visit_seq(fields, default, &seq_expecting);
+    let (value_ty, construct_value) = match output {
+        Output::Value(construct) => (
+            quote!(#name #ty_generics),
+            construct_value(params, construct, fields, default),
+        ),
+        Output::Partial(partial) => (
+            quote!((#partial #ty_generics, ::struct_validator::StructValidator)),
+            partial_value(params, partial, fields, default),
+        ),
+    };
+    let deserialize = deserialize(quote! {
+        __Visitor {
+            marker: ::std::marker::PhantomData,
+            lifetime: ::std::marker::PhantomData,
+        }
+    });
+
+    quote! {{
+        #[allow(non_camel_case_types)]
+        enum __Field {
+            #(#variants,)*
+            #other_variant,
+        }
+
+        struct __FieldVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for __FieldVisitor {
+            type Value = __Field;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str(#field_expecting)
+            }
+
+            fn visit_str<__E>(self, value: &str) -> ::std::result::Result<__Field, __E>
+            where
+                __E: serde::de::Error,
+            {
+                match value {
+                    #(#field_patterns => ::std::result::Result::Ok(__Field::#variants),)*
+                    _ => ::std::result::Result::Ok(__Field::#other_value),
+                }
+            }
+
+            #visit_identifier
+        }
+
+        impl<'de> serde::Deserialize<'de> for __Field {
+            #[inline]
+            fn deserialize<__D>(__deserializer: __D) -> ::std::result::Result<Self, __D::Error>
+            where
+                __D: serde::Deserializer<'de>,
+            {
+                serde::Deserializer::deserialize_identifier(__deserializer, __FieldVisitor)
+            }
+        }
+
+        struct __Visitor #de_impl_generics #de_where_clause {
+            marker: ::std::marker::PhantomData<#name #ty_generics>,
+            lifetime: ::std::marker::PhantomData<&'de ()>,
+        }
+
+        impl #de_impl_generics serde::de::Visitor<'de> for __Visitor #de_ty_generics #de_where_clause {
+            type Value = #value_ty;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+
+            fn visit_map<__V>(self, mut __map: __V) -> ::std::result::Result<Self::Value, __V::Error>
+            where
+                __V: serde::de::MapAccess<'de>,
+            {
+                #(let mut #locals: ::std::option::Option<#tys> = ::std::option::Option::None;)*
+                let mut __errors = ::struct_validator::StructValidator::new();
+                let mut __invalid = ::struct_validator::StructValidator::new();
+                while let ::std::option::Option::Some(__key) =
+                    serde::de::MapAccess::next_key::<__Field>(&mut __map)?
+                {
+                    match __key {
+                        #(__Field::#variants => {
+                            let __value = ::struct_validator::__private::nest_warnings(
+                                &mut __errors,
+                                #names,
+                                || serde::de::MapAccess::next_value_seed(&mut __map, #seeds),
+                            );
+                            match __value {
+                                ::std::result::Result::Ok(__value) => {
+                                    #checks
+                                    #locals = ::std::option::Option::Some(__value);
+                                }
+                                ::std::result::Result::Err(__err) => {
+                                    __errors.insert_error(#names, __err);
+                                }
+                            }
+                        })*
+                        #other_arm
+                    }
+                }
+                #(#check_missing)*
+
+                #construct_value
+            }
+
+            fn visit_seq<__V>(self, mut __seq: __V) -> ::std::result::Result<Self::Value, __V::Error>
+            where
+                __V: serde::de::SeqAccess<'de>,
+            {
+                #visit_seq
+                #construct_value
+            }
+        }
+
+        const FIELDS: &[&str] = &[#(#names),*];
+        #deserialize
+    }}
+}
+
+/// Block deserializing `fields` from a sequence into `construct { 0: .., 1: .. }`, collecting the
+/// errors of every element under its position.
+fn deserialize_tuple(
+    params: &Params,
+    construct: TokenStream,
+    fields: &[Field],
+    default: &attr::Default,
+    subject: &str,
+    deserialize: impl FnOnce(TokenStream) -> TokenStream,
+) -> TokenStream {
+    let name = params.name;
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    let (de_impl_generics, de_ty_generics, de_where_clause) = params.de_generics.split_for_impl();
+    let len = fields
+        .iter()
+        .filter(|f| !f.attrs.skip_deserializing)
+        .count();
+    let expecting = format!("{} with {} elements", subject, len);
+    let visit_seq = visit_seq(fields, default, &expecting);
+    let construct_value = construct_value(params, construct, fields, default);
+    let deserialize = deserialize(quote! {
+        __Visitor {
+            marker: ::std::marker::PhantomData,
+            lifetime: ::std::marker::PhantomData,
+        }
+    });
+
+    quote! {{
+        struct __Visitor #de_impl_generics #de_where_clause {
+            marker: ::std::marker::PhantomData<#name #ty_generics>,
+            lifetime: ::std::marker::PhantomData<&'de ()>,
+        }
+
+        impl #de_impl_generics serde::de::Visitor<'de> for __Visitor #de_ty_generics #de_where_clause {
+            type Value = #name #ty_generics;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str(#subject)
+            }
+
+            fn visit_seq<__V>(self, mut __seq: __V) -> ::std::result::Result<Self::Value, __V::Error>
+            where
+                __V: serde::de::SeqAccess<'de>,
+            {
+                #visit_seq
+                #construct_value
+            }
+        }
+
+        #deserialize
+    }}
+}
+
+/// Statements reading `fields` from `__seq` into their locals, recording the error of every
+/// element in `__errors`, and an invalid length error if elements are missing or left over.
+fn visit_seq(fields: &[Field], default: &attr::Default, expecting: &str) -> TokenStream {
+    let deserialized: Vec<_> = fields
+        .iter()
+        .filter(|f| !f.attrs.skip_deserializing)
+        .collect();
+    let len = deserialized.len();
+    let reads = deserialized.iter().enumerate().map(|(i, f)| {
+        let local = &f.local;
+        let name = &f.attrs.name;
+        let ty = f.ty;
+        let seed = f.seed();
+        let check = f.check();
+        quote! {
+            let mut #local: ::std::option::Option<#ty> = ::std::option::Option::None;
+            if __len == #i {
+                let __value = ::struct_validator::__private::nest_warnings(
+                    &mut __errors,
+                    #name,
+                    || serde::de::SeqAccess::next_element_seed(&mut __seq, #seed),
+                );
+                match __value {
+                    ::std::result::Result::Ok(::std::option::Option::Some(__value)) => {
+                        #check
+                        #local = ::std::option::Option::Some(__value);
+                        __len += 1;
+                    }
+                    ::std::result::Result::Ok(::std::option::Option::None) => {}
+                    ::std::result::Result::Err(__err) => {
+                        __errors.insert_error(#name, __err);
+                        __len += 1;
+                    }
+                }
+            }
+        }
+    });
+    let missing = deserialized
+        .iter()
+        .filter(|f| f.fallback(default).is_none())
+        .map(|f| {
+            let local = &f.local;
+            let name = &f.attrs.name;
+            quote!(|| #local.is_none() && !__errors.contains(#name))
+        });
+
+    quote! {
+        let mut __len = 0usize;
+        let mut __errors = ::struct_validator::StructValidator::new();
+        let mut __invalid = ::struct_validator::StructValidator::new();
+        #(#reads)*
+        if __len == #len {
+            while let ::std::option::Option::Some(serde::de::IgnoredAny) =
+                serde::de::SeqAccess::next_element(&mut __seq)?
+            {
+                __len += 1;
+            }
+        }
+        if __len > #len #(#missing)* {
+            __errors.insert_field_error(
+                ::struct_validator::StructValidator::ROOT,
+                ::struct_validator::FieldError::invalid_length(__len, #expecting),
+            );
+        }
+    }
+}
+
+/// Statements returning the collected `__errors`, if any, or else `construct { .. }` built from
+/// the locals of `fields`.
+fn construct_value(
+    params: &Params,
+    construct: TokenStream,
+    fields: &[Field],
+    default: &attr::Default,
+) -> TokenStream {
+    let members: Vec<_> = fields.iter().map(|f| &f.member).collect();
+    let locals: Vec<_> = fields.iter().map(|f| &f.local).collect();
+    let unwrap_fields = fields.iter().map(|f| {
+        let local = &f.local;
+        let name = &f.attrs.name;
+        if f.attrs.skip_deserializing {
+            let fallback = f.fallback(default);
+            return quote!(let #local = #fallback;);
+        }
+        let fallback = f.fallback(default).unwrap_or_else(|| {
+            quote! {
+                return ::std::result::Result::Err(serde::de::Error::missing_field(#name))
+            }
+        });
+        quote! {
+            let #local = match #local {
+                ::std::option::Option::Some(__value) => __value,
+                ::std::option::Option::None => #fallback,
+            };
+        }
+    });
+    let let_default = let_default(params, default);
+    let check_struct =
+        validate::check_struct(quote!(__invalid), quote!(&__value), params.rules, fields);
+
+    // Values that only broke rules can still be built, to run the struct-level rules on them.
+    quote! {
+        if !__errors.is_empty() {
+            __errors.merge(__invalid);
+            return ::std::result::Result::Err(__errors.into_de_error());
+        }
+        #let_default
+        #(#unwrap_fields)*
+        let __value = #construct {
+            #(#members: #locals),*
+        };
+        #check_struct
+        if !__invalid.is_empty() {
+            __errors.merge(__invalid);
+            return ::std::result::Result::Err(__errors.into_de_error());
+        }
+        ::struct_validator::__private::emit_warnings(__errors);
+        ::std::result::Result::Ok(__value)
+    }
+}
+
+/// Statements returning `partial { .. }` with each field set to its value, or to its errors if it
+/// failed or is missing, along with the collected `__errors`.
+fn partial_value(
+    params: &Params,
+    partial: &Ident,
+    fields: &[Field],
+    default: &attr::Default,
+) -> TokenStream {
+    let members: Vec<_> = fields.iter().map(|f| &f.member).collect();
+    let locals: Vec<_> = fields.iter().map(|f| &f.local).collect();
+    let partial_fields = fields.iter().map(|f| {
+        let local = &f.local;
+        let name = &f.attrs.name;
+        let fallback = f.fallback(default);
+        if f.attrs.skip_deserializing {
+            return quote!(let #local = ::std::result::Result::Ok(#fallback););
+        }
+        let fallback = fallback.map(|fallback| {
+            quote! {
+                ::std::option::Option::None if !__errors.contains(#name) => {
+                    ::std::result::Result::Ok(#fallback)
+                }
+            }
+        });
+        quote! {
+            let #local = match #local {
+                ::std::option::Option::Some(__value) if !__errors.contains(#name) => {
+                    ::std::result::Result::Ok(__value)
+                }
+                #fallback
+                _ => ::std::result::Result::Err(__errors.nested(#name)),
+            };
+        }
+    });
+    let let_default = let_default(params, default);
+    let check_struct = check_partial(params, fields, default);
+
+    quote! {
+        #let_default
+        #check_struct
+        __errors.merge(__invalid);
+        #(#partial_fields)*
+        ::std::result::Result::Ok((#partial { #(#members: #locals),* }, __errors))
+    }
+}
+
+/// Statements running the struct-level rules on the value built from the locals of `fields`, if
+/// they all deserialized, then putting the fields back in their locals. Like `construct_value`,
+/// values that only broke field rules are still checked.
+fn check_partial(params: &Params, fields: &[Field], default: &attr::Default) -> TokenStream {
+    let name = params.name;
+    let check_struct =
+        validate::check_struct(quote!(__invalid), quote!(&__value), params.rules, fields);
+    if check_struct.is_empty() {
+        return check_struct;
+    }
+    let members = fields.iter().map(|f| &f.member);
+    let values = fields.iter().map(|f| {
+        let local = &f.local;
+        let name = &f.attrs.name;
+        if f.attrs.skip_deserializing {
+            return f.fallback(default).unwrap_or_default();
+        }
+        let fallback = f.fallback(default).unwrap_or_else(|| {
+            quote! {
+                return ::std::result::Result::Err(serde::de::Error::missing_field(#name))
+            }
+        });
+        quote! {
+            match ::std::option::Option::take(&mut #local) {
+                ::std::option::Option::Some(__value) => __value,
+                ::std::option::Option::None => #fallback,
+            }
+        }
+    });
+    let deserialized: Vec<_> = fields
+        .iter()
+        .filter(|f| !f.attrs.skip_deserializing)
+        .collect();
+    let deserialized_members = deserialized.iter().map(|f| &f.member);
+    let checked: Vec<_> = deserialized
+        .iter()
+        .map(|f| format_ident!("{}_checked", f.local))
+        .collect();
+    let locals = deserialized.iter().map(|f| &f.local);
+
+    quote! {
+        if __errors.is_empty() {
+            let __value = #name {
+                #(#members: #values,)*
+            };
+            #check_struct
+            let #name { #(#deserialized_members: #checked,)* .. } = __value;
+            #(#locals = ::std::option::Option::Some(#checked);)*
+        }
+    }
+}
+
+/// Statement binding the container default to `__default`, if the container has one.
+fn let_default(params: &Params, default: &attr::Default) -> Option<TokenStream> {
+    let name = params.name;
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    match default {
+        attr::Default::None => None,
+        attr::Default::Default => Some(quote! {
+            let __default: #name #ty_generics = ::std::default::Default::default();
+        }),
+        attr::Default::Path(path) => Some(quote! {
+            let __default: #name #ty_generics = #path();
+        }),
+    }
+}
+
+/// A newtype struct deserializes as its inner value, whose errors are forwarded unchanged. The
+/// errors of its rules are its own too.
+fn deserialize_newtype(params: &Params, field: &Field, name_str: &str) -> TokenStream {
+    let name = params.name;
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    let (de_impl_generics, de_ty_generics, de_where_clause) = params.de_generics.split_for_impl();
+    let ty = field.ty;
+    let expecting = format!("tuple struct {}", name_str);
+    let check_value = check_value(params, field, quote!(#name(__value)));
+
+    quote! {
+        struct __Visitor #de_impl_generics #de_where_clause {
+            marker: ::std::marker::PhantomData<#name #ty_generics>,
+            lifetime: ::std::marker::PhantomData<&'de ()>,
+        }
+
+        impl #de_impl_generics serde::de::Visitor<'de> for __Visitor #de_ty_generics #de_where_clause {
+            type Value = #name #ty_generics;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+
+            fn visit_newtype_struct<__E>(self, __e: __E) -> ::std::result::Result<Self::Value, __E::Error>
+            where
+                __E: serde::Deserializer<'de>,
+            {
+                let __value: #ty = serde::Deserialize::deserialize(__e)?;
+                #check_value
+            }
+
+            fn visit_seq<__V>(self, mut __seq: __V) -> ::std::result::Result<Self::Value, __V::Error>
+            where
+                __V: serde::de::SeqAccess<'de>,
+            {
+                let __value: #ty = match serde::de::SeqAccess::next_element(&mut __seq)? {
+                    ::std::option::Option::Some(__value) => __value,
+                    ::std::option::Option::None => {
+                        return ::std::result::Result::Err(serde::de::Error::invalid_length(0, &self))
+                    }
+                };
+                #check_value
+            }
+        }
+
+        serde::Deserializer::deserialize_newtype_struct(
+            __deserializer,
+            #name_str,
+            __Visitor {
+                marker: ::std::marker::PhantomData,
+                lifetime: ::std::marker::PhantomData,
+            },
+        )
+    }
+}
+
+/// `#[serde(transparent)]`: the struct deserializes as its only deserialized field, whose errors
+/// are forwarded unchanged. The errors of its rules are its own too.
+fn deserialize_transparent(
+    params: &Params,
+    fields: &[Field],
+    default: &attr::Default,
+) -> syn::Result<TokenStream> {
+    let mut deserialized = fields.iter().filter(|f| !f.attrs.skip_deserializing);
+    let field = match (deserialized.next(), deserialized.next()) {
+        (Some(field), None) => field,
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "#[serde(transparent)] requires exactly one field that is not skipped",
+            ))
+        }
+    };
+    let name = params.name;
+    let member = &field.member;
+    let ty = field.ty;
+    let others = fields
+        .iter()
+        .filter(|f| f.attrs.skip_deserializing)
+        .map(|f| {
+            let member = &f.member;
+            let fallback = f.fallback(default);
+            quote!(#member: #fallback)
+        });
+    let let_default = let_default(params, default);
+    let check_value = check_value(
+        params,
+        field,
+        quote! {
+            #name {
+                #member: __value,
+                #(#others,)*
+            }
+        },
+    );
+
+    Ok(quote! {
+        let __value = <#ty as serde::Deserialize>::deserialize(__deserializer)?;
+        #let_default
+        #check_value
+    })
+}
+
+/// Statements checking `__value`, the only field of a newtype or transparent struct, against its
+/// rules, then the struct built from it by `construct` against the struct-level rules, returning
+/// the struct or the errors of both under the root.
+fn check_value(params: &Params, field: &Field, construct: TokenStream) -> TokenStream {
+    let check = field.check_under(quote!(::struct_validator::StructValidator::ROOT));
+    let check_struct = validate::check_struct(
+        quote!(__invalid),
+        quote!(&__value),
+        params.rules,
+        std::slice::from_ref(field),
+    );
+
+    quote! {
+        let mut __invalid = ::struct_validator::StructValidator::new();
+        #check
+        let __value = #construct;
+        #check_struct
+        if !__invalid.is_empty() {
+            return ::std::result::Result::Err(__invalid.into_de_error());
+        }
+        ::std::result::Result::Ok(__value)
+    }
+}
+
+fn deserialize_enum(
+    params: &Params,
+    container: &attr::Container,
+    data: &DataEnum,
+) -> syn::Result<TokenStream> {
+    let variants = data
+        .variants
+        .iter()
+        .enumerate()
+        .map(|(i, variant)| {
+            let internally_tagged = matches!(container.tagging, attr::Tagging::Internal { .. });
+            if internally_tagged && is_tuple(&variant.fields) {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "internally tagged enums do not support tuple variants",
+                ));
+            }
+            Ok(Variant {
+                ident: &variant.ident,
+                fields: &variant.fields,
+                tag: format_ident!("__variant{}", i),
+                attrs: attr::Variant::from_attrs(&variant.ident, &variant.attrs, container)?,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let variants: Vec<_> = variants
+        .into_iter()
+        .filter(|v| !v.attrs.skip_deserializing)
+        .collect();
+
+    match &container.tagging {
+        attr::Tagging::External => deserialize_externally_tagged(params, container, &variants),
+        attr::Tagging::Internal { tag } => {
+            deserialize_tagged(params, container, &variants, tag, None)
+        }
+        attr::Tagging::Adjacent { tag, content } => {
+            deserialize_tagged(params, container, &variants, tag, Some(content))
+        }
+        attr::Tagging::Untagged => deserialize_untagged(params, container, &variants),
+    }
+}
+
+fn variant_patterns<'a>(variants: &'a [Variant]) -> impl Iterator<Item = TokenStream> + 'a {
+    variants
+        .iter()
+        .map(|v| str_pattern(&v.attrs.name, &v.attrs.aliases))
+}
+
+fn str_pattern(name: &str, aliases: &[String]) -> TokenStream {
+    quote!(#name #(| #aliases)*)
+}
+
+fn bytes_pattern(name: &str, aliases: &[String]) -> TokenStream {
+    let names = iter::once(name)
+        .chain(aliases.iter().map(String::as_str))
+        .map(|name| LitByteStr::new(name.as_bytes(), Span::call_site()));
+    quote!(#(#names)|*)
+}
+
+/// Identifier visitor methods for keys given as bytes or as the index of the field or variant,
+/// as binary formats do. Anything unmatched goes through `visit_str`, which handles unknown keys.
+fn visit_identifier(
+    ty: TokenStream,
+    variants: &[&Ident],
+    bytes_patterns: impl Iterator<Item = TokenStream>,
+) -> TokenStream {
+    let indexes = 0..variants.len() as u64;
+    quote! {
+        fn visit_u64<__E>(self, value: u64) -> ::std::result::Result<#ty, __E>
+        where
+            __E: serde::de::Error,
+        {
+            match value {
+                #(#indexes => ::std::result::Result::Ok(#ty::#variants),)*
+                _ => self.visit_str(&::std::string::ToString::to_string(&value)),
+            }
+        }
+
+        fn visit_borrowed_str<__E>(self, value: &'de str) -> ::std::result::Result<#ty, __E>
+        where
+            __E: serde::de::Error,
+        {
+            self.visit_str(value)
+        }
+
+        fn visit_bytes<__E>(self, value: &[u8]) -> ::std::result::Result<#ty, __E>
+        where
+            __E: serde::de::Error,
+        {
+            match value {
+                #(#bytes_patterns => ::std::result::Result::Ok(#ty::#variants),)*
+                _ => self.visit_str(&::std::string::String::from_utf8_lossy(value)),
+            }
+        }
+
+        fn visit_borrowed_bytes<__E>(self, value: &'de [u8]) -> ::std::result::Result<#ty, __E>
+        where
+            __E: serde::de::Error,
+        {
+            self.visit_bytes(value)
+        }
+    }
+}
+
+/// `{ "Variant": content }`, the errors in the content being reported under the variant name.
+fn deserialize_externally_tagged(
+    params: &Params,
+    container: &attr::Container,
+    variants: &[Variant],
+) -> syn::Result<TokenStream> {
+    let name = params.name;
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    let (de_impl_generics, de_ty_generics, de_where_clause) = params.de_generics.split_for_impl();
+    let name_str = &container.name;
+    let expecting = format!("enum {}", name_str);
+    let variant_expecting = format!("a variant of {}", name_str);
+
+    let tags: Vec<_> = variants.iter().map(|v| &v.tag).collect();
+    let names: Vec<_> = variants.iter().map(|v| v.attrs.name.as_str()).collect();
+    let patterns = variant_patterns(variants);
+    let visit_identifier = visit_identifier(
+        quote!(__Variant),
+        &tags,
+        variants
+            .iter()
+            .map(|v| bytes_pattern(&v.attrs.name, &v.attrs.aliases)),
+    );
+    let arms = variants
+        .iter()
+        .map(|v| {
+            let ident = v.ident;
+            let tag = &v.tag;
+            let variant_name = &v.attrs.name;
+            let content = match v.fields {
+                Fields::Unit => quote! {
+                    ::std::result::Result::map(
+                        serde::de::VariantAccess::unit_variant(__variant),
+                        |()| #name::#ident,
+                    )
+                },
+                Fields::Unnamed(_) if v.fields.len() == 1 => {
+                    let fields = struct_fields(v.fields, v.attrs.rename_all)?;
+                    let ty = fields[0].ty;
+                    check_newtype_variant(
+                        &fields[0],
+                        quote!(#name::#ident),
+                        quote!(serde::de::VariantAccess::newtype_variant::<#ty>(__variant)),
+                    )
+                }
+                Fields::Unnamed(_) => {
+                    let fields = struct_fields(v.fields, v.attrs.rename_all)?;
+                    let len = fields.len();
+                    deserialize_tuple(
+                        params,
+                        quote!(#name::#ident),
+                        &fields,
+                        &attr::Default::None,
+                        &format!("tuple variant {}::{}", name_str, variant_name),
+                        |visitor| {
+                            quote! {
+                                serde::de::VariantAccess::tuple_variant(__variant, #len, #visitor)
+                            }
+                        },
+                    )
+                }
+                Fields::Named(_) => {
+                    let fields = struct_fields(v.fields, v.attrs.rename_all)?;
+                    deserialize_fields(
+                        params,
+                        container,
+                        Output::Value(quote!(#name::#ident)),
+                        &fields,
+                        &attr::Default::None,
+                        &format!("{}::{}", name_str, variant_name),
+                        |visitor| {
+                            quote! {
+                                serde::de::VariantAccess::struct_variant(__variant, FIELDS, #visitor)
+                            }
+                        },
+                    )
+                }
+            };
+            Ok(quote! {
+                (__Variant::#tag, __variant) => ::std::result::Result::map_err(
+                    ::struct_validator::__private::nest_variant_warnings(#variant_name, || #content),
+                    |__err| ::struct_validator::__private::nest_error(#variant_name, __err),
+                ),
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        #[allow(non_camel_case_types)]
+        enum __Variant {
+            #(#tags,)*
+        }
+
+        struct __VariantVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for __VariantVisitor {
+            type Value = __Variant;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str(#variant_expecting)
+            }
+
+            fn visit_str<__E>(self, value: &str) -> ::std::result::Result<__Variant, __E>
+            where
+                __E: serde::de::Error,
+            {
+                match value {
+                    #(#patterns => ::std::result::Result::Ok(__Variant::#tags),)*
+                    _ => ::std::result::Result::Err(::struct_validator::__private::unknown_variant(
+                        ::struct_validator::StructValidator::ROOT,
+                        value,
+                        VARIANTS,
+                    )),
+                }
+            }
+
+            #visit_identifier
+        }
+
+        impl<'de> serde::Deserialize<'de> for __Variant {
+            #[inline]
+            fn deserialize<__D>(__deserializer: __D) -> ::std::result::Result<Self, __D::Error>
+            where
+                __D: serde::Deserializer<'de>,
+            {
+                serde::Deserializer::deserialize_identifier(__deserializer, __VariantVisitor)
+            }
+        }
+
+        struct __EnumVisitor #de_impl_generics #de_where_clause {
+            marker: ::std::marker::PhantomData<#name #ty_generics>,
+            lifetime: ::std::marker::PhantomData<&'de ()>,
+        }
+
+        impl #de_impl_generics serde::de::Visitor<'de> for __EnumVisitor #de_ty_generics #de_where_clause {
+            type Value = #name #ty_generics;
+
+            fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                formatter.write_str(#expecting)
+            }
+
+            fn visit_enum<__A>(self, __data: __A) -> ::std::result::Result<Self::Value, __A::Error>
+            where
+                __A: serde::de::EnumAccess<'de>,
+            {
+                match serde::de::EnumAccess::variant::<__Variant>(__data)? {
+                    #(#arms)*
+                }
+            }
+        }
+
+        const VARIANTS: &[&str] = &[#(#names),*];
+        serde::Deserializer::deserialize_enum(
+            __deserializer,
+            #name_str,
+            VARIANTS,
+            __EnumVisitor {
+                marker: ::std::marker::PhantomData,
+                lifetime: ::std::marker::PhantomData,
+            },
+        )
+    })
+}
+
+/// Expression checking the value `deserialize` results in against the rules of `field`, the only
+/// field of a newtype variant, then wrapping it with `construct`. The errors of the rules are the
+/// variant's own, like the errors of its value.
+fn check_newtype_variant(
+    field: &Field,
+    construct: TokenStream,
+    deserialize: TokenStream,
+) -> TokenStream {
+    let check = field.check_under(quote!(::struct_validator::StructValidator::ROOT));
+    quote! {
+        match #deserialize {
+            ::std::result::Result::Ok(__value) => {
+                let mut __invalid = ::struct_validator::StructValidator::new();
+                #check
+                if __invalid.is_empty() {
+                    ::std::result::Result::Ok(#construct(__value))
+                } else {
+                    ::std::result::Result::Err(__invalid.into_de_error())
+                }
+            }
+            ::std::result::Result::Err(__err) => ::std::result::Result::Err(__err),
+        }
+    }
+}
+
+/// Expression deserializing the content of a variant from `value`, a deserializer replaying the
+/// buffered input.
+fn deserialize_variant_content(
+    params: &Params,
+    container: &attr::Container,
+    variant: &Variant,
+    value: TokenStream,
+) -> syn::Result<TokenStream> {
+    let name = params.name;
+    let ident = variant.ident;
+    Ok(match variant.fields {
+        Fields::Unit => quote! {
+            ::std::result::Result::map(
+                <() as serde::Deserialize>::deserialize(#value),
+                |()| #name::#ident,
+            )
+        },
+        Fields::Unnamed(_) if variant.fields.len() == 1 => {
+            let fields = struct_fields(variant.fields, variant.attrs.rename_all)?;
+            let ty = fields[0].ty;
+            check_newtype_variant(
+                &fields[0],
+                quote!(#name::#ident),
+                quote!(<#ty as serde::Deserialize>::deserialize(#value)),
+            )
+        }
+        Fields::Unnamed(_) => {
+            let fields = struct_fields(variant.fields, variant.attrs.rename_all)?;
+            deserialize_tuple(
+                params,
+                quote!(#name::#ident),
+                &fields,
+                &attr::Default::None,
+                &format!("tuple variant {}::{}", container.name, variant.attrs.name),
+                |visitor| quote!(serde::Deserializer::deserialize_seq(#value, #visitor)),
+            )
+        }
+        Fields::Named(_) => {
+            let fields = struct_fields(variant.fields, variant.attrs.rename_all)?;
+            deserialize_fields(
+                params,
+                container,
+                Output::Value(quote!(#name::#ident)),
+                &fields,
+                &attr::Default::None,
+                &format!("{}::{}", container.name, variant.attrs.name),
+                |visitor| quote!(serde::Deserializer::deserialize_map(#value, #visitor)),
+            )
+        }
+    })
+}
+
+/// `{ "tag": "Variant", ...fields }` when internally tagged, `{ "tag": "Variant", "content": .. }`
+/// when adjacently tagged. Content errors are reported flat, or under the content key.
+fn deserialize_tagged(
+    params: &Params,
+    container: &attr::Container,
+    variants: &[Variant],
+    tag: &str,
+    content: Option<&String>,
+) -> syn::Result<TokenStream> {
+    let name = params.name;
+    let names: Vec<_> = variants.iter().map(|v| v.attrs.name.as_str()).collect();
+    let patterns = variant_patterns(variants);
+    let error_key = match content {
+        Some(content) => quote!(#content),
+        None => quote!(::struct_validator::StructValidator::ROOT),
+    };
+    let take_content = content.map(|content| {
+        quote! {
+            let __content = __object.remove(#content).map(
+                ::struct_validator::__private::ContentDeserializer::<__D::Error>::new,
+            );
+        }
+    });
+    let arms = variants
+        .iter()
+        .map(|v| {
+            if let Fields::Unit = v.fields {
+                let ident = v.ident;
+                return Ok(quote!(::std::result::Result::Ok(#name::#ident)));
+            }
+            let deserialize = match content {
+                Some(content) => {
+                    let deserialize =
+                        deserialize_variant_content(params, container, v, quote!(__content))?;
+                    quote! {
+                        match __content {
+                            ::std::option::Option::Some(__content) => {
+                                ::struct_validator::__private::nest_variant_warnings(
+                                    #content,
+                                    || #deserialize,
+                                )
+                            }
+                            ::std::option::Option::None => {
+                                return ::std::result::Result::Err(
+                                    ::struct_validator::__private::missing(#content),
+                                )
+                            }
+                        }
+                    }
+                }
+                None => deserialize_variant_content(
+                    params,
+                    container,
+                    v,
+                    quote! {
+                        ::struct_validator::__private::ContentDeserializer::<__D::Error>::new(
+                            __object.into_content(),
+                        )
+                    },
+                )?,
+            };
+            Ok(quote! {
+                ::std::result::Result::map_err(
+                    #deserialize,
+                    |__err| ::struct_validator::__private::nest_error(#error_key, __err),
+                )
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        const VARIANTS: &[&str] = &[#(#names),*];
+        let mut __object =
+            <::struct_validator::__private::ContentMap as serde::Deserialize>::deserialize(
+                __deserializer,
+            )?;
+        let __tag: ::std::string::String =
+            ::struct_validator::__private::take_tag(&mut __object, #tag)?;
+        #take_content
+        match __tag.as_str() {
+            #(#patterns => #arms,)*
+            _ => ::std::result::Result::Err(::struct_validator::__private::unknown_variant(
+                #tag,
+                &__tag,
+                VARIANTS,
+            )),
+        }
+    })
+}
+
+/// Tries every variant in order, reporting the errors of the closest one if none matches.
+fn deserialize_untagged(
+    params: &Params,
+    container: &attr::Container,
+    variants: &[Variant],
+) -> syn::Result<TokenStream> {
+    let name = params.name;
+    let (_, ty_generics, _) = params.generics.split_for_impl();
+    let name_str = &container.name;
+    let attempts = variants
+        .iter()
+        .map(|v| {
+            let deserialize = deserialize_variant_content(
+                params,
+                container,
+                v,
+                quote! {
+                    ::struct_validator::__private::ContentDeserializer::<__D::Error>::new(
+                        ::std::clone::Clone::clone(&__content),
+                    )
+                },
+            )?;
+            Ok(quote! {
+                let __attempt: ::std::result::Result<#name #ty_generics, __D::Error> = #deserialize;
+                match __attempt {
+                    ::std::result::Result::Ok(__value) => return ::std::result::Result::Ok(__value),
+                    ::std::result::Result::Err(__err) => __attempts.push(
+                        ::struct_validator::StructValidator::from_de_error(&__err),
+                    ),
+                }
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        let __content =
+            <::struct_validator::__private::Content as serde::Deserialize>::deserialize(__deserializer)?;
+        let mut __attempts = ::std::vec::Vec::new();
+        #(#attempts)*
+        ::std::result::Result::Err(::struct_validator::__private::untagged_error(#name_str, __attempts))
+    })
+}
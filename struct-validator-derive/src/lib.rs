@@ -0,0 +1,25 @@
+extern crate proc_macro;
+
+mod attr;
+mod case;
+mod de;
+mod validate;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+#[proc_macro_derive(ValidatedDeserialize, attributes(serde, validate))]
+pub fn derive_validated_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    de::expand_derive_deserialize(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(Validate, attributes(serde, validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    validate::expand_derive_validate(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,529 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Data, DeriveInput, Expr, ExprPath, Fields, Ident, Member, Type, TypeParamBound,
+};
+
+use crate::attr::{self, Rule, StructRule};
+use crate::de::{is_option, struct_fields, Field};
+
+pub fn expand_derive_validate(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = attr::Container::from_attrs(&input.ident, &input.attrs)?;
+    let fields = match &input.data {
+        Data::Struct(data) => struct_fields(&data.fields, container.rename_all)?,
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "Validate only supports structs",
+            ))
+        }
+    };
+    let newtype = matches!(&input.data, Data::Struct(data) if matches!(&data.fields, Fields::Unnamed(_)))
+        && fields.len() == 1;
+    let checks = fields.iter().map(|f| {
+        let member = &f.member;
+        // A transparent or newtype struct is its field, whose errors are its own.
+        let name = if container.transparent || newtype {
+            quote!(::struct_validator::StructValidator::ROOT)
+        } else {
+            let name = &f.attrs.name;
+            quote!(#name)
+        };
+        check_rules(
+            quote!(__errors),
+            quote!(&self.#member),
+            f.ty,
+            name,
+            &f.attrs.rules,
+        )
+    });
+    let struct_checks = check_struct(quote!(__errors), quote!(self), &container.rules, &fields);
+
+    let validate_with = expand_validate_with(input, &container, &fields);
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::struct_validator::Validate for #name #ty_generics #where_clause {
+            fn validate(
+                &self,
+            ) -> ::std::result::Result<(), ::struct_validator::StructValidator> {
+                let mut __errors = ::struct_validator::StructValidator::new();
+                #(#checks)*
+                #struct_checks
+                if __errors.is_empty() {
+                    ::std::result::Result::Ok(())
+                } else {
+                    ::std::result::Result::Err(__errors)
+                }
+            }
+        }
+
+        #validate_with
+    })
+}
+
+/// `ValidateWith` impl running the rules that take the context after the others, and
+/// `ValidateAsync` impl running the async rules concurrently after those, for structs with a
+/// context or rules that take one.
+fn expand_validate_with(
+    input: &DeriveInput,
+    container: &attr::Container,
+    fields: &[Field],
+) -> TokenStream {
+    let with_checks: Vec<_> = fields
+        .iter()
+        .flat_map(|f| {
+            f.attrs.rules.iter().filter_map(move |rule| match rule {
+                Rule::CustomWith(path) => Some(check_with(f, path)),
+                _ => None,
+            })
+        })
+        .collect();
+    let with_schemas: Vec<_> = container
+        .rules
+        .iter()
+        .filter_map(|rule| match rule {
+            StructRule::SchemaWith(path) => Some(path),
+            _ => None,
+        })
+        .collect();
+    let field_rules: Vec<_> = fields
+        .iter()
+        .flat_map(|f| {
+            f.attrs.rules.iter().filter_map(move |rule| match rule {
+                Rule::AsyncCustom(path) => Some((f, path)),
+                _ => None,
+            })
+        })
+        .collect();
+    let schemas: Vec<_> = container
+        .rules
+        .iter()
+        .filter_map(|rule| match rule {
+            StructRule::AsyncSchema(path) => Some(path),
+            _ => None,
+        })
+        .collect();
+    if container.context.is_none()
+        && with_checks.is_empty()
+        && with_schemas.is_empty()
+        && field_rules.is_empty()
+        && schemas.is_empty()
+    {
+        return TokenStream::new();
+    }
+
+    let checks: Vec<_> = (0..field_rules.len())
+        .map(|i| format_ident!("__check{}", i))
+        .collect();
+    let check_futures = field_rules.iter().zip(&checks).map(|((f, path), check)| {
+        let member = &f.member;
+        let name = &f.attrs.name;
+        let value = if is_option(f.ty) {
+            quote!(::std::option::Option::as_ref(&self.#member))
+        } else {
+            quote!(::std::option::Option::Some(&self.#member))
+        };
+        quote! {
+            let #check = {
+                let __value = #value;
+                let __run = !__errors.contains(#name);
+                async move {
+                    match __value {
+                        ::std::option::Option::Some(__value) if __run => {
+                            ::std::option::Option::Some(::std::result::Result::map_err(
+                                #path(__value, __ctx).await,
+                                <_ as ::std::convert::Into<::struct_validator::FieldError>>::into,
+                            ))
+                        }
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            };
+        }
+    });
+    let record_checks = field_rules.iter().zip(&checks).map(|((f, _), check)| {
+        let name = &f.attrs.name;
+        quote! {
+            if let ::std::option::Option::Some(::std::result::Result::Err(__error)) = #check {
+                __errors.insert_field_error(#name, __error);
+            }
+        }
+    });
+    let schema_outputs: Vec<_> = (0..schemas.len())
+        .map(|i| format_ident!("__schema{}", i))
+        .collect();
+    let outputs: Vec<_> = checks.iter().chain(&schema_outputs).collect();
+    let join = if outputs.is_empty() {
+        TokenStream::new()
+    } else {
+        quote! {
+            #(let #schema_outputs = #schemas(self, __ctx);)*
+            let (#(#outputs,)*) = ::struct_validator::__private::futures::join!(#(#outputs),*);
+        }
+    };
+
+    let name = &input.ident;
+    let mut ctx = container
+        .context
+        .clone()
+        .unwrap_or_else(|| parse_quote!(()));
+    // `dyn Trait` means `dyn Trait + 'static` in the impl header but not behind the reference of
+    // the method, so the bound is spelled out for both to agree.
+    if let Type::TraitObject(object) = &mut ctx {
+        if !object
+            .bounds
+            .iter()
+            .any(|bound| matches!(bound, TypeParamBound::Lifetime(_)))
+        {
+            object.bounds.push(parse_quote!('static));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::struct_validator::ValidateWith<#ctx> for #name #ty_generics #where_clause {
+            fn validate_with(
+                &self,
+                __ctx: &(#ctx),
+            ) -> ::std::result::Result<(), ::struct_validator::StructValidator> {
+                let mut __errors = match ::struct_validator::Validate::validate(self) {
+                    ::std::result::Result::Ok(()) => ::struct_validator::StructValidator::new(),
+                    ::std::result::Result::Err(__errors) => __errors,
+                };
+                #(#with_checks)*
+                #(
+                    if let ::std::result::Result::Err(__nested) = #with_schemas(self, __ctx) {
+                        __errors.merge_unfailed(__nested);
+                    }
+                )*
+                if __errors.is_empty() {
+                    ::std::result::Result::Ok(())
+                } else {
+                    ::std::result::Result::Err(__errors)
+                }
+            }
+        }
+
+        impl #impl_generics ::struct_validator::ValidateAsync<#ctx> for #name #ty_generics #where_clause {
+            fn validate_async<'__a>(
+                &'__a self,
+                __ctx: &'__a (#ctx),
+            ) -> impl ::std::future::Future<
+                Output = ::std::result::Result<(), ::struct_validator::StructValidator>,
+            > + '__a {
+                async move {
+                    let mut __errors = match ::struct_validator::ValidateWith::validate_with(self, __ctx) {
+                        ::std::result::Result::Ok(()) => ::struct_validator::StructValidator::new(),
+                        ::std::result::Result::Err(__errors) => __errors,
+                    };
+                    #(#check_futures)*
+                    #join
+                    #(#record_checks)*
+                    #(
+                        if let ::std::result::Result::Err(__nested) = #schema_outputs {
+                            __errors.merge_unfailed(__nested);
+                        }
+                    )*
+                    if __errors.is_empty() {
+                        ::std::result::Result::Ok(())
+                    } else {
+                        ::std::result::Result::Err(__errors)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Statement checking the field `f` of `self` against the `custom_with` rule `path`, unless the
+/// field already failed.
+fn check_with(f: &Field, path: &ExprPath) -> TokenStream {
+    let member = &f.member;
+    let name = &f.attrs.name;
+    let value = if is_option(f.ty) {
+        quote!(::std::option::Option::as_ref(&self.#member))
+    } else {
+        quote!(::std::option::Option::Some(&self.#member))
+    };
+    quote! {
+        if let ::std::option::Option::Some(__value) = #value {
+            if !__errors.contains(#name) {
+                if let ::std::result::Result::Err(__error) = #path(__value, __ctx) {
+                    __errors.insert_field_error(#name, ::std::convert::Into::into(__error));
+                }
+            }
+        }
+    }
+}
+
+/// Statements checking `value`, a reference to a field of type `ty`, against `rules`, recording
+/// the errors in the `errors` validator under `name`. The rules of an `Option` apply to its
+/// value, if any.
+pub fn check_rules<'a>(
+    errors: TokenStream,
+    value: TokenStream,
+    ty: &Type,
+    name: TokenStream,
+    rules: impl IntoIterator<Item = &'a Rule>,
+) -> TokenStream {
+    let mut rules = rules
+        .into_iter()
+        .filter(|rule| {
+            !is_conditional(rule) && !matches!(rule, Rule::CustomWith(_) | Rule::AsyncCustom(_))
+        })
+        .peekable();
+    if rules.peek().is_none() {
+        return TokenStream::new();
+    }
+    let checks = rules.map(|rule| {
+        let result = match rule {
+            Rule::Range { min, max } => {
+                let min = optional(min);
+                let max = optional(max);
+                quote!(::struct_validator::rules::range(__value, #min, #max))
+            }
+            Rule::Length { min, max, equal } => {
+                let min = optional(min);
+                let max = optional(max);
+                let equal = optional(equal);
+                quote!(::struct_validator::rules::length(__value, #min, #max, #equal))
+            }
+            Rule::Regex(pattern) => quote! {{
+                static __REGEX: ::std::sync::OnceLock<::struct_validator::__private::Regex> =
+                    ::std::sync::OnceLock::new();
+                let __regex = ::struct_validator::__private::regex(&__REGEX, #pattern);
+                ::struct_validator::rules::regex(__value, __regex)
+            }},
+            Rule::Email => quote!(::struct_validator::rules::email(__value)),
+            Rule::Url => quote!(::struct_validator::rules::url(__value)),
+            Rule::Custom(path) => quote! {
+                ::std::result::Result::map_err(#path(__value), ::std::convert::Into::into)
+            },
+            Rule::Nested => {
+                return quote! {
+                    if let ::std::result::Result::Err(__nested) =
+                        ::struct_validator::Validate::validate(__value)
+                    {
+                        #errors.extend_nested(#name, __nested);
+                    }
+                }
+            }
+            Rule::CustomWith(_)
+            | Rule::AsyncCustom(_)
+            | Rule::RequiredIf { .. }
+            | Rule::RequiredUnless { .. }
+            | Rule::RequiredWith(_) => unreachable!(),
+        };
+        quote! {
+            if let ::std::result::Result::Err(__error) = #result {
+                #errors.insert_field_error(#name, __error);
+            }
+        }
+    });
+
+    if is_option(ty) {
+        quote! {
+            if let ::std::option::Option::Some(__value) = ::std::option::Option::as_ref(#value) {
+                #(#checks)*
+            }
+        }
+    } else {
+        quote! {{
+            let __value = #value;
+            #(#checks)*
+        }}
+    }
+}
+
+/// Statements checking `value`, a reference to the whole struct, against the conditional
+/// requirements of its `fields` and its struct-level `rules`, recording the errors in the `errors`
+/// validator. Schema errors for fields that already failed are dropped.
+pub fn check_struct(
+    errors: TokenStream,
+    value: TokenStream,
+    rules: &[StructRule],
+    fields: &[Field],
+) -> TokenStream {
+    let requirements = fields.iter().flat_map(|f| {
+        f.attrs
+            .rules
+            .iter()
+            .filter(|rule| is_conditional(rule))
+            .map(|rule| check_requirement(&errors, &value, f, rule, fields))
+            .collect::<Vec<_>>()
+    });
+    let checks = rules.iter().map(|rule| match rule {
+        StructRule::Custom(function) => quote! {
+            if let ::std::result::Result::Err(__error) = #function(#value) {
+                #errors.insert_field_error(
+                    ::struct_validator::StructValidator::ROOT,
+                    ::std::convert::Into::into(__error),
+                );
+            }
+        },
+        StructRule::Schema {
+            function,
+            fields: depends,
+        } => {
+            let names = depends.iter().map(|ident| match find_field(fields, ident) {
+                Ok(f) => {
+                    let name = &f.attrs.name;
+                    quote!(#name)
+                }
+                Err(error) => error,
+            });
+            let check = quote! {
+                if let ::std::result::Result::Err(__nested) = #function(#value) {
+                    #errors.merge_unfailed(__nested);
+                }
+            };
+            if depends.is_empty() {
+                check
+            } else {
+                quote! {
+                    if #(!#errors.contains(#names))&&* {
+                        #check
+                    }
+                }
+            }
+        }
+        StructRule::SchemaWith(_) | StructRule::AsyncSchema(_) => TokenStream::new(),
+        StructRule::Group {
+            kind,
+            fields: group,
+        } => {
+            let function = match kind {
+                attr::Group::ExactlyOne => quote!(exactly_one_of),
+                attr::Group::AtMostOne => quote!(at_most_one_of),
+                attr::Group::AtLeastOne => quote!(at_least_one_of),
+            };
+            let entries = presence(&value, group, fields);
+            quote! {
+                let __group: &[(&str, bool)] = #entries;
+                if let ::std::result::Result::Err(__error) =
+                    ::struct_validator::rules::#function(__group)
+                {
+                    for (__name, _) in __group {
+                        #errors.insert_field_error(*__name, ::std::clone::Clone::clone(&__error));
+                    }
+                }
+            }
+        }
+    });
+    quote!(#(#requirements)* #(#checks)*)
+}
+
+fn is_conditional(rule: &Rule) -> bool {
+    matches!(
+        rule,
+        Rule::RequiredIf { .. } | Rule::RequiredUnless { .. } | Rule::RequiredWith(_)
+    )
+}
+
+/// Statement checking the conditional requirement `rule` of `field`.
+fn check_requirement(
+    errors: &TokenStream,
+    value: &TokenStream,
+    field: &Field,
+    rule: &Rule,
+    fields: &[Field],
+) -> TokenStream {
+    if !is_option(field.ty) {
+        return syn::Error::new_spanned(
+            field.ty,
+            "conditional requirements need an `Option` field",
+        )
+        .to_compile_error();
+    }
+    let member = &field.member;
+    let name = &field.attrs.name;
+    let present = quote!(::std::option::Option::is_some(&(#value).#member));
+    let result = match rule {
+        Rule::RequiredIf {
+            field: other,
+            values,
+        }
+        | Rule::RequiredUnless {
+            field: other,
+            values,
+        } => {
+            let other = match find_field(fields, other) {
+                Ok(other) => other,
+                Err(error) => return error,
+            };
+            let other_member = &other.member;
+            let other_name = &other.attrs.name;
+            let equals = if is_option(other.ty) {
+                quote! {
+                    match ::std::option::Option::as_ref(&(#value).#other_member) {
+                        ::std::option::Option::Some(__other) => false #(|| *__other == #values)*,
+                        ::std::option::Option::None => false,
+                    }
+                }
+            } else {
+                quote!(false #(|| (#value).#other_member == #values)*)
+            };
+            let function = match rule {
+                Rule::RequiredIf { .. } => quote!(required_if),
+                _ => quote!(required_unless),
+            };
+            quote! {
+                ::struct_validator::rules::#function(
+                    #present,
+                    #equals,
+                    #other_name,
+                    &[#(&#values),*],
+                )
+            }
+        }
+        Rule::RequiredWith(others) => {
+            let others = presence(value, others, fields);
+            quote!(::struct_validator::rules::required_with(#present, #others))
+        }
+        _ => unreachable!(),
+    };
+    quote! {
+        if let ::std::result::Result::Err(__error) = #result {
+            #errors.insert_field_error(#name, __error);
+        }
+    }
+}
+
+/// `&[(name, present)]` for the fields named `idents`, those that aren't an `Option` being always
+/// present.
+fn presence(value: &TokenStream, idents: &[Ident], fields: &[Field]) -> TokenStream {
+    let entries = idents.iter().map(|ident| match find_field(fields, ident) {
+        Ok(field) => {
+            let member = &field.member;
+            let name = &field.attrs.name;
+            let present = if is_option(field.ty) {
+                quote!(::std::option::Option::is_some(&(#value).#member))
+            } else {
+                quote!(true)
+            };
+            quote!((#name, #present))
+        }
+        Err(error) => error,
+    });
+    quote!(&[#(#entries),*])
+}
+
+/// The field named `ident`, or a compile error pointing at `ident` if there is none.
+fn find_field<'f, 'a>(
+    fields: &'f [Field<'a>],
+    ident: &Ident,
+) -> Result<&'f Field<'a>, TokenStream> {
+    let member = Member::Named(ident.clone());
+    fields
+        .iter()
+        .find(|f| f.member == member)
+        .ok_or_else(|| syn::Error::new(ident.span(), "unknown field").to_compile_error())
+}
+
+fn optional(expr: &Option<Expr>) -> TokenStream {
+    match expr {
+        Some(expr) => quote!(::std::option::Option::Some(#expr)),
+        None => quote!(::std::option::Option::None),
+    }
+}
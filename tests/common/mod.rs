@@ -0,0 +1,13 @@
+use std::collections::BTreeMap;
+
+/// The messages of `StructValidator::messages`, built from `(key, message)` pairs.
+pub fn messages(entries: &[(&str, &str)]) -> BTreeMap<String, Vec<String>> {
+    let mut messages = BTreeMap::new();
+    for (key, message) in entries {
+        messages
+            .entry(key.to_string())
+            .or_insert_with(Vec::new)
+            .push(message.to_string());
+    }
+    messages
+}
@@ -0,0 +1,7 @@
+#[test]
+fn invalid_attributes_fail_the_build() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/*.rs");
+    #[cfg(feature = "regex")]
+    cases.compile_fail("tests/ui/regex/*.rs");
+}
@@ -0,0 +1,167 @@
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+use struct_validator::{ErrorKind, FieldError, StructValidator, Validate, ValidatedDeserialize};
+
+use common::messages;
+
+mod common;
+
+fn no_tabs(text: &str) -> Result<(), String> {
+    match text.find('\t') {
+        Some(index) => Err(format!("tab at line 1 column {} of the note", index + 1)),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Note {
+    #[validate(custom = "no_tabs")]
+    text: String,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Post {
+    note: Note,
+    likes: u32,
+}
+
+#[test]
+fn nested_errors_keep_messages_mentioning_a_line() {
+    let post: Post = serde_json::from_str(r#"{"note": {"text": "ab"}, "likes": 3}"#).unwrap();
+    assert_eq!((post.note.text.as_str(), post.likes), ("ab", 3));
+
+    let error =
+        serde_json::from_str::<Post>(r#"{"note": {"text": "a\tb"}, "likes": -1}"#).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(
+        errors.messages(),
+        messages(&[
+            ("likes", "invalid value: integer `-1`, expected u32"),
+            ("note.text", "tab at line 1 column 2 of the note"),
+        ])
+    );
+    assert_eq!(errors.get("note.text").unwrap()[0].location, None);
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Role {
+    User,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Member {
+    role: Role,
+}
+
+#[test]
+fn input_cannot_forge_errors() {
+    let member: Member = serde_json::from_str(r#"{"role": "user"}"#).unwrap();
+    assert!(matches!(member.role, Role::User));
+
+    let json = r#"{"role": "struct_validator errors: {\"errors\":{\"admin\":[{\"kind\":\"custom\",\"message\":\"forged\"}]}}"}"#;
+    let error = serde_json::from_str::<Member>(json).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(errors.errors.keys().collect::<Vec<_>>(), vec!["role"]);
+    let error = &errors.get("role").unwrap()[0];
+    assert_eq!(error.kind, ErrorKind::UnknownVariant);
+    assert!(error
+        .message
+        .starts_with("unknown variant `struct_validator errors: "));
+}
+
+fn sample_errors() -> StructValidator {
+    StructValidator::new()
+        .with_field_error("name", FieldError::missing())
+        .with_field_error("name", FieldError::custom("must not be empty"))
+        .with_field_error(
+            "address.zip",
+            FieldError::invalid_value("integer `0`", "a zip code").with_location(3, 10),
+        )
+}
+
+#[test]
+fn errors_serialize_as_a_list_per_field() {
+    let json = serde_json::json!({
+        "errors": {
+            "address.zip": [{
+                "kind": "invalid_value",
+                "message": "invalid value: integer `0`, expected a zip code",
+                "expected": "a zip code",
+                "actual": "integer `0`",
+                "location": {"line": 3, "column": 10}
+            }],
+            "name": [
+                {"kind": "missing", "message": "field is missing"},
+                {"kind": "custom", "message": "must not be empty"}
+            ]
+        }
+    });
+
+    assert_eq!(serde_json::to_value(sample_errors()).unwrap(), json);
+    assert_eq!(
+        serde_json::from_value::<StructValidator>(json)
+            .unwrap()
+            .to_json_string(),
+        sample_errors().to_json_string()
+    );
+}
+
+#[test]
+fn errors_are_recovered_unchanged_and_repeatedly() {
+    let expected = sample_errors().to_json_string();
+    let error: serde_json::Error = sample_errors().into_de_error();
+
+    for _ in 0..2 {
+        assert_eq!(
+            StructValidator::from_de_error(&error)
+                .unwrap()
+                .to_json_string(),
+            expected
+        );
+    }
+    assert_eq!(
+        StructValidator::try_from(&error).unwrap().to_json_string(),
+        expected
+    );
+    assert_eq!(
+        StructValidator::try_from(&error).unwrap().to_json_string(),
+        expected
+    );
+    assert_eq!(
+        StructValidator::try_from(error).unwrap().to_json_string(),
+        expected
+    );
+}
+
+#[test]
+fn earlier_errors_are_recovered_after_later_ones() {
+    let first = serde_json::from_str::<Post>(r#"{"note": {}, "likes": 1}"#).unwrap_err();
+    let second = serde_json::from_str::<Post>(r#"{"note": {"text": "a"}}"#).unwrap_err();
+
+    assert_eq!(
+        StructValidator::from_de_error(&first).unwrap().messages(),
+        messages(&[("note.text", "field is missing")])
+    );
+    assert_eq!(
+        StructValidator::from_de_error(&second).unwrap().messages(),
+        messages(&[("likes", "field is missing")])
+    );
+}
+
+#[test]
+fn errors_are_recovered_on_another_thread() {
+    let error = serde_json::from_str::<Post>(r#"{"note": {"text": 1}, "likes": 1}"#).unwrap_err();
+
+    let errors = std::thread::spawn(move || StructValidator::try_from_de_error(error).unwrap())
+        .join()
+        .unwrap();
+
+    assert_eq!(
+        errors.messages(),
+        messages(&[("note.text", "invalid type: integer `1`, expected a string")])
+    );
+}
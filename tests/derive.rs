@@ -0,0 +1,792 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::value::{BytesDeserializer, Error as ValueError, MapDeserializer};
+use serde::de::{DeserializeOwned, IntoDeserializer};
+use serde::{Deserialize, Serialize};
+use struct_validator::{
+    deserialize_with_warnings, DeserializePartial, ErrorKind, StructValidator, ValidatedDeserialize,
+};
+
+use common::messages;
+
+mod common;
+
+fn errors<T>(json: &str) -> BTreeMap<String, Vec<String>>
+where
+    T: DeserializeOwned + std::fmt::Debug,
+{
+    let error = serde_json::from_str::<T>(json).unwrap_err();
+    StructValidator::try_from_de_error(error)
+        .unwrap()
+        .messages()
+}
+
+fn default_port() -> u16 {
+    8080
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Server {
+    host: String,
+    name: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default = "default_port")]
+    port: u16,
+}
+
+#[test]
+fn optional_and_default_fields_may_be_absent() {
+    let server: Server = serde_json::from_str(r#"{"host": "localhost"}"#).unwrap();
+
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.name, None);
+    assert!(server.tags.is_empty());
+    assert_eq!(server.port, 8080);
+}
+
+#[test]
+fn required_fields_are_still_reported_missing() {
+    assert_eq!(
+        errors::<Server>(r#"{"name": null, "port": "x"}"#),
+        messages(&[
+            ("host", "field is missing"),
+            ("port", "invalid type: string \"x\", expected u16"),
+        ])
+    );
+}
+
+#[derive(Debug, Default, ValidatedDeserialize)]
+#[serde(default)]
+struct Limits {
+    max: u32,
+    label: String,
+}
+
+#[test]
+fn container_default_fills_absent_fields() {
+    let limits: Limits = serde_json::from_str(r#"{"max": 3}"#).unwrap();
+
+    assert_eq!(limits.max, 3);
+    assert_eq!(limits.label, "");
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(rename_all = "camelCase")]
+struct Profile {
+    first_name: String,
+    #[serde(rename = "mail", alias = "email")]
+    email_address: String,
+    #[serde(skip_deserializing)]
+    cached: Option<u32>,
+    r#type: u8,
+}
+
+#[test]
+fn fields_are_matched_by_their_wire_names() {
+    let profile: Profile =
+        serde_json::from_str(r#"{"firstName": "Ann", "email": "a@b.c", "cached": 3, "type": 1}"#)
+            .unwrap();
+
+    assert_eq!(profile.first_name, "Ann");
+    assert_eq!(profile.email_address, "a@b.c");
+    assert_eq!(profile.cached, None);
+    assert_eq!(profile.r#type, 1);
+}
+
+#[test]
+fn errors_are_keyed_by_wire_names() {
+    assert_eq!(
+        errors::<Profile>(r#"{"first_name": "Ann", "mail": 1}"#),
+        messages(&[
+            ("firstName", "field is missing"),
+            ("mail", "invalid type: integer `1`, expected a string"),
+            ("type", "field is missing"),
+        ])
+    );
+}
+
+fn upper<S: serde::Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_uppercase())
+}
+
+#[derive(Debug, Serialize, ValidatedDeserialize)]
+struct Badge {
+    #[serde(serialize_with = "upper")]
+    code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing)]
+    secret: String,
+    kind: BadgeKind,
+}
+
+#[derive(Debug, Serialize, ValidatedDeserialize)]
+enum BadgeKind {
+    Gold,
+    #[serde(skip_serializing)]
+    Legacy,
+}
+
+#[test]
+fn serialization_attributes_are_accepted() {
+    let badge: Badge =
+        serde_json::from_str(r#"{"code": "ab", "secret": "s", "kind": "Gold"}"#).unwrap();
+    assert_eq!(
+        serde_json::to_string(&badge).unwrap(),
+        r#"{"code":"AB","kind":"Gold"}"#
+    );
+    assert_eq!(badge.secret, "s");
+    assert!(badge.label.is_none());
+
+    let badge: Badge =
+        serde_json::from_str(r#"{"code": "ab", "secret": "s", "kind": "Legacy"}"#).unwrap();
+    assert!(matches!(badge.kind, BadgeKind::Legacy));
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(deny_unknown_fields)]
+struct Signup {
+    email: String,
+    password: String,
+}
+
+#[test]
+fn unknown_fields_can_be_rejected() {
+    let signup: Signup = serde_json::from_str(r#"{"email": "a@b.c", "password": "x"}"#).unwrap();
+    assert_eq!(
+        (signup.email.as_str(), signup.password.as_str()),
+        ("a@b.c", "x")
+    );
+
+    let error = serde_json::from_str::<Signup>(r#"{"emial": "a@b.c", "password": "x", "x": 1}"#)
+        .unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(
+        errors.messages(),
+        messages(&[
+            ("email", "field is missing"),
+            ("emial", "unknown field, did you mean `email`?"),
+            ("x", "unknown field"),
+        ])
+    );
+    assert_eq!(errors.get("x").unwrap()[0].kind, ErrorKind::UnknownField);
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[validate(unknown_fields = "warn")]
+struct Login {
+    email: String,
+}
+
+#[test]
+fn unknown_fields_can_be_reported_as_warnings() {
+    let login: Login = serde_json::from_str(r#"{"email": "a@b.c", "emial": 1}"#).unwrap();
+    assert_eq!(login.email, "a@b.c");
+
+    let error = serde_json::from_str::<Login>(r#"{"emial": "a@b.c"}"#).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(
+        errors.messages(),
+        messages(&[("email", "field is missing")])
+    );
+    assert_eq!(
+        errors.warnings["emial"][0].message,
+        "unknown field, did you mean `email`?"
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Session {
+    login: Login,
+    previous: Vec<Login>,
+}
+
+#[test]
+fn warnings_can_be_returned_with_the_value() {
+    let json = r#"{
+        "login": {"email": "a@b.c", "emial": 1},
+        "previous": [{"email": "c@d.e"}, {"email": "e@f.g", "x": 2}]
+    }"#;
+    let (session, warnings): (Session, _) =
+        deserialize_with_warnings(&mut serde_json::Deserializer::from_str(json)).unwrap();
+    assert_eq!(session.login.email, "a@b.c");
+    assert_eq!(session.previous[1].email, "e@f.g");
+
+    assert!(warnings.is_empty());
+    assert_eq!(
+        warnings.warnings.keys().collect::<Vec<_>>(),
+        vec!["login.emial", "previous[1].x"]
+    );
+    assert_eq!(
+        warnings.warnings["login.emial"][0].message,
+        "unknown field, did you mean `email`?"
+    );
+}
+
+thread_local! {
+    static LOGGED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Logger recording the messages logged by each test thread.
+struct Recorder;
+
+impl log::Log for Recorder {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        LOGGED.with(|logged| logged.borrow_mut().push(record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+static RECORDER: Recorder = Recorder;
+
+#[test]
+fn logged_warnings_keep_their_path() {
+    log::set_logger(&RECORDER).unwrap();
+    log::set_max_level(log::LevelFilter::Warn);
+
+    let json = r#"{
+        "login": {"email": "a@b.c", "emial": 1},
+        "previous": [{"email": "c@d.e"}, {"email": "e@f.g", "x": 2}]
+    }"#;
+    let session: Session = serde_json::from_str(json).unwrap();
+    assert_eq!(session.login.email, "a@b.c");
+    assert_eq!(session.previous.len(), 2);
+
+    assert_eq!(
+        LOGGED.with(|logged| logged.take()),
+        [
+            "login.emial: unknown field, did you mean `email`?",
+            "previous[1].x: unknown field",
+        ]
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Item {
+    price: u32,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    total: u32,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Borrowed<'a> {
+    name: &'a str,
+    count: u32,
+}
+
+#[test]
+fn generic_structs_are_supported() {
+    let page: Page<Item> =
+        serde_json::from_str(r#"{"items": [{"price": 5}], "total": 1}"#).unwrap();
+
+    assert_eq!(page.items[0].price, 5);
+    assert_eq!(page.total, 1);
+    assert_eq!(
+        errors::<Page<u8>>(r#"{"items": [1]}"#),
+        messages(&[("total", "field is missing")])
+    );
+}
+
+#[test]
+fn borrowed_fields_are_supported() {
+    let input = String::from(r#"{"name": "zero-copy", "count": 1}"#);
+    let borrowed: Borrowed = serde_json::from_str(&input).unwrap();
+
+    assert_eq!(borrowed.name, "zero-copy");
+    assert_eq!(borrowed.count, 1);
+    assert_eq!(
+        StructValidator::try_from_de_error(
+            serde_json::from_str::<Borrowed>(r#"{"count": -1}"#).unwrap_err()
+        )
+        .unwrap()
+        .messages(),
+        messages(&[
+            ("count", "invalid value: integer `-1`, expected u32"),
+            ("name", "field is missing"),
+        ])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Card {
+    number: String,
+    cvc: u16,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(rename_all = "snake_case")]
+enum Payment {
+    Cash,
+    Voucher(u32),
+    Card { number: String, cvc: u16 },
+}
+
+#[test]
+fn externally_tagged_enums_report_errors_under_the_variant() {
+    match serde_json::from_str(r#"{"voucher": 7}"#).unwrap() {
+        Payment::Voucher(code) => assert_eq!(code, 7),
+        payment => panic!("unexpected {:?}", payment),
+    }
+    match serde_json::from_str(r#"{"card": {"number": "4242", "cvc": 1}}"#).unwrap() {
+        Payment::Card { number, cvc } => assert_eq!((number.as_str(), cvc), ("4242", 1)),
+        payment => panic!("unexpected {:?}", payment),
+    }
+    assert!(matches!(
+        serde_json::from_str(r#""cash""#).unwrap(),
+        Payment::Cash
+    ));
+
+    assert_eq!(
+        errors::<Payment>(r#"{"card": {"cvc": "x"}}"#),
+        messages(&[
+            ("card.cvc", "invalid type: string \"x\", expected u16"),
+            ("card.number", "field is missing"),
+        ])
+    );
+    assert_eq!(
+        errors::<Payment>(r#"{"cheque": {}}"#),
+        messages(&[(
+            StructValidator::ROOT,
+            "unknown variant `cheque`, expected one of `cash`, `voucher`, `card`"
+        )])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Method {
+    Card(Card),
+    Transfer { iban: String },
+    Cash,
+}
+
+#[test]
+fn internally_tagged_enums_report_errors_of_the_selected_variant() {
+    let method: Method = serde_json::from_str(r#"{"type": "transfer", "iban": "X"}"#).unwrap();
+    assert!(matches!(method, Method::Transfer { iban } if iban == "X"));
+    match serde_json::from_str(r#"{"type": "card", "number": "4242", "cvc": 1}"#).unwrap() {
+        Method::Card(card) => assert_eq!((card.number.as_str(), card.cvc), ("4242", 1)),
+        method => panic!("unexpected {:?}", method),
+    }
+
+    assert_eq!(
+        errors::<Method>(r#"{"type": "card", "cvc": -1}"#),
+        messages(&[
+            ("cvc", "invalid value: integer `-1`, expected u16"),
+            ("number", "field is missing"),
+        ])
+    );
+    assert_eq!(
+        errors::<Method>(r#"{"type": "cheque"}"#),
+        messages(&[(
+            "type",
+            "unknown variant `cheque`, expected one of `card`, `transfer`, `cash`"
+        )])
+    );
+    assert_eq!(
+        errors::<Method>(r#"{"iban": "X"}"#),
+        messages(&[("type", "field is missing")])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(tag = "kind", content = "data")]
+enum Event {
+    Opened { id: u32 },
+    Closed,
+}
+
+#[test]
+fn adjacently_tagged_enums_report_errors_under_the_content() {
+    let event: Event = serde_json::from_str(r#"{"kind": "Opened", "data": {"id": 1}}"#).unwrap();
+    assert!(matches!(event, Event::Opened { id: 1 }));
+    assert!(matches!(
+        serde_json::from_str(r#"{"kind": "Closed"}"#).unwrap(),
+        Event::Closed
+    ));
+
+    assert_eq!(
+        errors::<Event>(r#"{"kind": "Opened", "data": {}}"#),
+        messages(&[("data.id", "field is missing")])
+    );
+    assert_eq!(
+        errors::<Event>(r#"{"kind": "Opened"}"#),
+        messages(&[("data", "field is missing")])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(untagged)]
+enum Contact {
+    Email { email: String },
+    Phone { phone: String, country: u16 },
+}
+
+#[test]
+fn untagged_enums_report_the_closest_variant() {
+    match serde_json::from_str(r#"{"phone": "555", "country": 1}"#).unwrap() {
+        Contact::Phone { phone, country } => assert_eq!((phone.as_str(), country), ("555", 1)),
+        contact => panic!("unexpected {:?}", contact),
+    }
+    match serde_json::from_str(r#"{"email": "a@b.c"}"#).unwrap() {
+        Contact::Email { email } => assert_eq!(email, "a@b.c"),
+        contact => panic!("unexpected {:?}", contact),
+    }
+
+    assert_eq!(
+        errors::<Contact>(r#"{"phone": "555", "country": "x"}"#),
+        messages(&[
+            (
+                StructValidator::ROOT,
+                "data did not match any variant of untagged enum Contact"
+            ),
+            ("country", "invalid type: string \"x\", expected u16"),
+        ])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(tag = "type")]
+enum Label<'a> {
+    Text { text: &'a str },
+    Link { title: &'a str, url: &'a str },
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(tag = "kind", content = "data")]
+enum Note<'a> {
+    Text(&'a str),
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(untagged)]
+enum Alias<'a> {
+    Named { name: &'a str },
+    Id(u32),
+}
+
+#[test]
+fn buffered_enums_keep_borrowed_data() {
+    match serde_json::from_str(r#"{"title": "Docs", "type": "Link", "url": "/docs"}"#).unwrap() {
+        Label::Link { title, url } => assert_eq!((title, url), ("Docs", "/docs")),
+        label => panic!("unexpected {:?}", label),
+    }
+    assert!(matches!(
+        serde_json::from_str(r#"{"type": "Text", "text": "hi"}"#).unwrap(),
+        Label::Text { text: "hi" }
+    ));
+    let Note::Text(text) = serde_json::from_str(r#"{"kind": "Text", "data": "hi"}"#).unwrap();
+    assert_eq!(text, "hi");
+    match serde_json::from_str(r#"{"name": "ann"}"#).unwrap() {
+        Alias::Named { name } => assert_eq!(name, "ann"),
+        alias => panic!("unexpected {:?}", alias),
+    }
+    assert!(matches!(serde_json::from_str("7").unwrap(), Alias::Id(7)));
+
+    let error = serde_json::from_str::<Label>(r#"{"type": "Text", "text": 1}"#).unwrap_err();
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        messages(&[(
+            "text",
+            "invalid type: integer `1`, expected a borrowed string"
+        )])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Point(i32, u8, String);
+
+#[test]
+fn tuple_structs_report_errors_by_position() {
+    let point: Point = serde_json::from_str(r#"[1, 2, "a"]"#).unwrap();
+    assert_eq!((point.0, point.1, point.2.as_str()), (1, 2, "a"));
+
+    assert_eq!(
+        errors::<Point>(r#"[1, -2, 3]"#),
+        messages(&[
+            ("1", "invalid value: integer `-2`, expected u8"),
+            ("2", "invalid type: integer `3`, expected a string"),
+        ])
+    );
+    assert_eq!(
+        errors::<Point>(r#"[1, "x"]"#),
+        messages(&[
+            ("1", "invalid type: string \"x\", expected u8"),
+            (
+                StructValidator::ROOT,
+                "invalid length 2, expected tuple struct Point with 3 elements"
+            ),
+        ])
+    );
+    assert_eq!(
+        errors::<Point>(r#"[1, 2, "a", 4]"#),
+        messages(&[(
+            StructValidator::ROOT,
+            "invalid length 4, expected tuple struct Point with 3 elements"
+        )])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Email(String);
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(transparent)]
+struct Quantity {
+    value: u32,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Contacts {
+    email: Email,
+    quantity: Quantity,
+    position: Point,
+}
+
+#[test]
+fn newtypes_forward_inner_errors_unchanged() {
+    let contacts: Contacts =
+        serde_json::from_str(r#"{"email": "a@b.c", "quantity": 3, "position": [0, 0, ""]}"#)
+            .unwrap();
+    assert_eq!(contacts.email.0, "a@b.c");
+    assert_eq!(contacts.quantity.value, 3);
+    assert_eq!(contacts.position.1, 0);
+
+    assert_eq!(
+        errors::<Contacts>(r#"{"email": 1, "quantity": "3", "position": [0, 0, 0]}"#),
+        messages(&[
+            ("email", "invalid type: integer `1`, expected a string"),
+            ("position.2", "invalid type: integer `0`, expected a string"),
+            ("quantity", "invalid type: string \"3\", expected u32"),
+        ])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+enum Shape {
+    Segment(i32, i32),
+}
+
+#[test]
+fn tuple_variants_report_errors_by_position() {
+    match serde_json::from_str(r#"{"Segment": [1, 2]}"#).unwrap() {
+        Shape::Segment(from, to) => assert_eq!((from, to), (1, 2)),
+    }
+    assert_eq!(
+        errors::<Shape>(r#"{"Segment": [1, "x"]}"#),
+        messages(&[("Segment.1", "invalid type: string \"x\", expected i32")])
+    );
+}
+
+#[test]
+fn structs_can_be_deserialized_from_sequences() {
+    let server: Server = serde_json::from_str(r#"["localhost", "web"]"#).unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.name.as_deref(), Some("web"));
+    assert_eq!(server.port, 8080);
+
+    assert_eq!(
+        errors::<Profile>(r#"["Ann", 1]"#),
+        messages(&[
+            ("mail", "invalid type: integer `1`, expected a string"),
+            (
+                StructValidator::ROOT,
+                "invalid length 2, expected struct Profile with 3 elements"
+            ),
+        ])
+    );
+    assert_eq!(
+        errors::<Signup>(r#"["a@b.c", "x", "y"]"#),
+        messages(&[(
+            StructValidator::ROOT,
+            "invalid length 3, expected struct Signup with 2 elements"
+        )])
+    );
+}
+
+#[test]
+fn fields_and_variants_can_be_identified_by_bytes_or_index() {
+    let by_index =
+        MapDeserializer::<_, ValueError>::new(vec![(0u64, "a@b.c"), (1, "x")].into_iter());
+    let signup = Signup::deserialize(by_index).unwrap();
+    assert_eq!(
+        (signup.email.as_str(), signup.password.as_str()),
+        ("a@b.c", "x")
+    );
+
+    let by_bytes = MapDeserializer::<_, ValueError>::new(
+        vec![
+            (BytesDeserializer::new(b"password"), "x"),
+            (BytesDeserializer::new(b"emial"), "a@b.c"),
+        ]
+        .into_iter(),
+    );
+    let error = Signup::deserialize(by_bytes).unwrap_err();
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        messages(&[
+            ("email", "field is missing"),
+            ("emial", "unknown field, did you mean `email`?"),
+        ])
+    );
+
+    let cash = Payment::deserialize(IntoDeserializer::<ValueError>::into_deserializer(0u32));
+    assert!(matches!(cash.unwrap(), Payment::Cash));
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Order {
+    items: Vec<Item>,
+    prices: HashMap<String, u32>,
+    stock: BTreeMap<u32, Item>,
+    slots: [u8; 2],
+}
+
+#[test]
+fn collections_report_errors_per_element() {
+    let order: Order = serde_json::from_str(
+        r#"{"items": [{"price": 1}], "prices": {"a": 2}, "stock": {"3": {"price": 4}}, "slots": [5, 6]}"#,
+    )
+    .unwrap();
+    assert_eq!(order.items[0].price, 1);
+    assert_eq!(order.prices["a"], 2);
+    assert_eq!(order.stock[&3].price, 4);
+    assert_eq!(order.slots, [5, 6]);
+
+    assert_eq!(
+        errors::<Order>(
+            r#"{
+                "items": [{"price": 1}, {"price": "x"}, {}],
+                "prices": {"a": 2, "b": -1},
+                "stock": {"3": {}, "4": {"price": 1}},
+                "slots": [1, 2, 3]
+            }"#
+        ),
+        messages(&[
+            ("items[1].price", "invalid type: string \"x\", expected u32"),
+            ("items[2].price", "field is missing"),
+            ("prices.b", "invalid value: integer `-1`, expected u32"),
+            ("slots", "invalid length 3, expected an array of length 2"),
+            ("stock.3.price", "field is missing"),
+        ])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Listing {
+    tags: Vec<String>,
+    scores: HashMap<String, u32>,
+    n: u32,
+}
+
+#[test]
+fn collections_skip_whole_invalid_elements() {
+    let listing: Listing =
+        serde_json::from_str(r#"{"tags": ["a", "b"], "scores": {"a": 1}, "n": 2}"#).unwrap();
+    assert_eq!(listing.tags, ["a", "b"]);
+    assert_eq!(listing.scores["a"], 1);
+    assert_eq!(listing.n, 2);
+
+    assert_eq!(
+        errors::<Listing>(
+            r#"{"tags": [{"a": 1}, "b", [2]], "scores": {"a": [1, {}], "b": 2}, "n": -1}"#
+        ),
+        messages(&[
+            ("n", "invalid value: integer `-1`, expected u32"),
+            ("scores.a", "invalid type: sequence, expected u32"),
+            ("tags[0]", "invalid type: map, expected a string"),
+            ("tags[2]", "invalid type: sequence, expected a string"),
+        ])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Cart {
+    items: Option<Vec<Item>>,
+    coupons: Option<BTreeMap<String, Item>>,
+    #[serde(default)]
+    codes: Box<[u32]>,
+}
+
+#[test]
+fn optional_collections_report_errors_per_element() {
+    let cart: Cart = serde_json::from_str(r#"{"items": null}"#).unwrap();
+    assert!(cart.items.is_none());
+    assert!(cart.coupons.is_none());
+    assert!(cart.codes.is_empty());
+    let cart: Cart = serde_json::from_str(r#"{"items": [{"price": 1}]}"#).unwrap();
+    assert_eq!(cart.items.unwrap()[0].price, 1);
+
+    assert_eq!(
+        errors::<Cart>(
+            r#"{"items": [{"price": 1}, {"price": "x"}], "coupons": {"a": {"price": -1}}, "codes": [1, true]}"#
+        ),
+        messages(&[
+            ("codes[1]", "invalid type: boolean `true`, expected u32"),
+            (
+                "coupons.a.price",
+                "invalid value: integer `-1`, expected u32"
+            ),
+            ("items[1].price", "invalid type: string \"x\", expected u32"),
+        ])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[validate(partial)]
+struct Patch {
+    name: String,
+    limits: Limits,
+    items: Vec<Item>,
+    note: Option<String>,
+}
+
+#[test]
+fn partial_deserialization_keeps_the_valid_fields() {
+    let mut deserializer = serde_json::Deserializer::from_str(
+        r#"{"limits": {"max": 2}, "items": [{"price": 1}, {"price": "x"}]}"#,
+    );
+    let (patch, errors) = Patch::deserialize_partial(&mut deserializer).unwrap();
+
+    assert_eq!(patch.limits.unwrap().max, 2);
+    assert_eq!(patch.note.unwrap(), None);
+    assert_eq!(
+        patch.name.unwrap_err().messages(),
+        messages(&[(StructValidator::ROOT, "field is missing")])
+    );
+    assert_eq!(
+        patch.items.unwrap_err().messages(),
+        messages(&[("[1].price", "invalid type: string \"x\", expected u32")])
+    );
+    assert_eq!(
+        errors.messages(),
+        messages(&[
+            ("items[1].price", "invalid type: string \"x\", expected u32"),
+            ("name", "field is missing"),
+        ])
+    );
+
+    let patch: Patch =
+        serde_json::from_str(r#"{"name": "a", "limits": {}, "items": [], "note": "b"}"#).unwrap();
+    assert_eq!(patch.name, "a");
+    assert_eq!(patch.limits.max, 0);
+    assert!(patch.items.is_empty());
+    assert_eq!(patch.note.as_deref(), Some("b"));
+}
@@ -0,0 +1,32 @@
+use std::collections::BTreeMap;
+
+use struct_validator::{deserialize_struct, StructValidator};
+
+#[derive(Debug)]
+struct Account {
+    name: String,
+    age: u8,
+}
+
+deserialize_struct!(Account, [name, age], "an account");
+
+#[test]
+fn collects_errors_of_every_field() {
+    let account: Account = serde_json::from_str(r#"{"name": "Ann", "age": 30, "x": 1}"#).unwrap();
+    assert_eq!((account.name.as_str(), account.age), ("Ann", 30));
+
+    let error = serde_json::from_str::<Account>(r#"{"age": -1}"#).unwrap_err();
+    let mut expected = BTreeMap::new();
+    expected.insert(
+        "age".to_string(),
+        vec!["invalid value: integer `-1`, expected u8".to_string()],
+    );
+    expected.insert("name".to_string(), vec!["field is missing".to_string()]);
+
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        expected
+    );
+}
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use struct_validator::{ErrorKind, Location, StructValidator, ValidatedDeserialize};
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Address {
+    zip: u32,
+    street: String,
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Config {
+    name: String,
+    port: u16,
+    address: Address,
+}
+
+fn assert_config_errors(errors: StructValidator) {
+    assert_eq!(
+        errors.errors.keys().collect::<Vec<_>>(),
+        vec!["address.street", "address.zip", "name", "port"]
+    );
+    assert_eq!(
+        errors.get("address.street").unwrap()[0].kind,
+        ErrorKind::Missing
+    );
+    assert_eq!(
+        errors.get("address.zip").unwrap()[0].kind,
+        ErrorKind::InvalidType
+    );
+    assert_eq!(errors.get("name").unwrap()[0].kind, ErrorKind::Missing);
+    assert_eq!(errors.get("port").unwrap()[0].kind, ErrorKind::InvalidType);
+    assert_eq!(
+        errors.get("port").unwrap()[0].message,
+        "invalid type: string \"http\", expected u16"
+    );
+}
+
+#[test]
+fn recovers_errors_from_serde_json() {
+    let error =
+        serde_json::from_str::<Config>(r#"{"port": "http", "address": {"zip": "x"}}"#).unwrap_err();
+
+    assert_config_errors(StructValidator::try_from_de_error(error).unwrap());
+}
+
+#[test]
+fn recovers_errors_from_serde_yaml() {
+    let error = serde_yaml::from_str::<Config>("port: http\naddress:\n  zip: x\n").unwrap_err();
+
+    assert_config_errors(StructValidator::try_from_de_error(error).unwrap());
+}
+
+#[test]
+fn recovers_errors_from_toml() {
+    let error = toml::from_str::<Config>("port = \"http\"\n[address]\nzip = \"x\"\n").unwrap_err();
+
+    assert_config_errors(StructValidator::try_from_de_error(error).unwrap());
+}
+
+#[test]
+fn records_location_of_field_errors() {
+    let yaml = "name: api\nport: http\naddress:\n  zip: x\n  street: Main\n";
+    let error = serde_yaml::from_str::<Config>(yaml).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(
+        errors.get("port").unwrap()[0].location,
+        Some(Location { line: 2, column: 7 })
+    );
+    assert_eq!(
+        errors.get("address.zip").unwrap()[0].location,
+        Some(Location { line: 4, column: 8 })
+    );
+
+    let json = "{\n  \"name\": \"api\",\n  \"port\": true\n}";
+    let error = serde_json::from_str::<Config>(json).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    // `true` spans columns 11 to 14, serde_json reports the position of its last character.
+    assert_eq!(
+        errors.get("port").unwrap()[0].location,
+        Some(Location {
+            line: 3,
+            column: 14
+        })
+    );
+    assert_eq!(errors.get("address").unwrap()[0].location, None);
+
+    let toml = "name = \"api\"\nport = true\n[address]\nzip = \"x\"\n";
+    let error = toml::from_str::<Config>(toml).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(errors.get("port").unwrap()[0].location, None);
+    assert_eq!(errors.get("address.zip").unwrap()[0].location, None);
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+struct Limits {
+    #[validate(range(max = 10))]
+    workers: u32,
+    retries: u32,
+}
+
+#[test]
+fn rule_errors_have_no_location() {
+    let limits: Limits = serde_yaml::from_str("workers: 2\nretries: 1\n").unwrap();
+    assert_eq!((limits.workers, limits.retries), (2, 1));
+
+    let yaml = "workers: 20
+retries: -1
+";
+    let error = serde_yaml::from_str::<Limits>(yaml).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(errors.get("workers").unwrap()[0].location, None);
+    assert_eq!(
+        errors.get("retries").unwrap()[0].location,
+        Some(Location {
+            line: 2,
+            column: 10
+        })
+    );
+
+    // `-1` spans columns 28 to 29.
+    let json = "{\"workers\": 20, \"retries\": -1}";
+    let error = serde_json::from_str::<Limits>(json).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(errors.get("workers").unwrap()[0].location, None);
+    assert_eq!(
+        errors.get("retries").unwrap()[0].location,
+        Some(Location {
+            line: 1,
+            column: 29
+        })
+    );
+}
+
+#[test]
+fn records_location_of_toml_document_errors() {
+    let toml = "name = \"api\"\nport = \"http\"\n";
+    let error = toml::from_str::<BTreeMap<String, u16>>(toml).unwrap_err();
+    let mut errors = StructValidator::new();
+    errors.insert_error("config", error);
+
+    let error = &errors.get("config").unwrap()[0];
+    assert_eq!(error.kind, ErrorKind::InvalidType);
+    assert_eq!(error.message, "invalid type: string \"api\", expected u16");
+    assert_eq!(error.location, Some(Location { line: 1, column: 8 }));
+}
+
+#[test]
+fn gives_back_errors_without_validator() {
+    let error = serde_yaml::from_str::<Config>("port: [").unwrap_err();
+
+    assert!(StructValidator::try_from_de_error(error).is_err());
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// A minimal self-describing format: nested maps of string keys with string or integer values.
+enum Value {
+    Str(&'static str),
+    Int(u64),
+    Map(Vec<(&'static str, Value)>),
+}
+
+impl<'de> de::Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Str(value) => visitor.visit_borrowed_str(value),
+            Value::Int(value) => visitor.visit_u64(value),
+            Value::Map(entries) => visitor.visit_map(Entries {
+                entries: entries.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Entries {
+    entries: std::vec::IntoIter<(&'static str, Value)>,
+    value: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for Entries {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.value.take().expect("value requested before key"))
+    }
+}
+
+#[test]
+fn recovers_errors_from_custom_deserializer() {
+    let input = Value::Map(vec![
+        ("port", Value::Str("http")),
+        ("address", Value::Map(vec![("zip", Value::Str("x"))])),
+    ]);
+    let error = <Config as serde::Deserialize>::deserialize(input).unwrap_err();
+
+    assert_config_errors(StructValidator::try_from_de_error(error).unwrap());
+}
+
+#[test]
+fn deserializes_valid_input_from_custom_deserializer() {
+    let input = Value::Map(vec![
+        ("name", Value::Str("api")),
+        ("port", Value::Int(8080)),
+        (
+            "address",
+            Value::Map(vec![
+                ("zip", Value::Int(8001)),
+                ("street", Value::Str("Main")),
+            ]),
+        ),
+    ]);
+    let config = <Config as serde::Deserialize>::deserialize(input).unwrap();
+
+    assert_eq!(config.name, "api");
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.address.zip, 8001);
+    assert_eq!(config.address.street, "Main");
+}
@@ -0,0 +1,9 @@
+use struct_validator::ValidatedDeserialize;
+
+#[derive(ValidatedDeserialize)]
+struct Coupon {
+    #[validate(regex = "^[A-Z]{2,$")]
+    code: String,
+}
+
+fn main() {}
@@ -0,0 +1,26 @@
+use struct_validator::ValidatedDeserialize;
+
+#[derive(serde::Deserialize)]
+struct Raw {
+    port: u16,
+}
+
+impl From<Raw> for FromRaw {
+    fn from(raw: Raw) -> Self {
+        FromRaw { port: raw.port }
+    }
+}
+
+#[derive(ValidatedDeserialize)]
+#[serde(from = "Raw")]
+struct FromRaw {
+    port: u16,
+}
+
+#[derive(ValidatedDeserialize)]
+#[serde(rename_all = "camelCase", bound = "T: Default")]
+struct Bound<T> {
+    value: T,
+}
+
+fn main() {}
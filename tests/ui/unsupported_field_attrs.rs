@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+
+use struct_validator::ValidatedDeserialize;
+
+fn parse_port<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    serde::Deserialize::deserialize(deserializer)
+}
+
+#[derive(ValidatedDeserialize)]
+struct Flattened {
+    name: String,
+    #[serde(flatten)]
+    extra: HashMap<String, String>,
+}
+
+#[derive(ValidatedDeserialize)]
+struct DeserializedWith {
+    #[serde(deserialize_with = "parse_port")]
+    port: u16,
+}
+
+#[derive(ValidatedDeserialize)]
+struct With {
+    #[serde(rename = "p", with = "parse_port")]
+    port: u16,
+}
+
+#[derive(ValidatedDeserialize)]
+struct Borrowed<'a> {
+    #[serde(borrow)]
+    name: &'a str,
+}
+
+fn main() {}
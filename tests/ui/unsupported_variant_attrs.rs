@@ -0,0 +1,17 @@
+use struct_validator::ValidatedDeserialize;
+
+#[derive(ValidatedDeserialize)]
+#[serde(tag = "type")]
+enum Shape {
+    Circle { radius: u32 },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(ValidatedDeserialize)]
+enum Size {
+    #[serde(deserialize_with = "parse_size")]
+    Custom(u32),
+}
+
+fn main() {}
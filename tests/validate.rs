@@ -0,0 +1,753 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use struct_validator::{
+    DeserializePartial, ErrorKind, FieldError, StructValidator, Validate, ValidateWith,
+    ValidatedDeserialize,
+};
+
+use common::messages;
+
+mod common;
+
+fn even(value: &u32) -> Result<(), FieldError> {
+    match value % 2 {
+        0 => Ok(()),
+        _ => Err(FieldError::custom("must be even")),
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Line {
+    #[validate(range(min = 1))]
+    quantity: u32,
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+    #[validate(length(min = 3, max = 8))]
+    user_name: String,
+    #[validate(email)]
+    email: String,
+    #[validate(url)]
+    homepage: Option<String>,
+    #[validate(range(min = 18, max = 130), custom = "even")]
+    age: u32,
+    #[validate(length(equal = 2), nested)]
+    lines: Vec<Line>,
+}
+
+#[test]
+fn valid_values_pass() {
+    let account: Account = serde_json::from_str(
+        r#"{
+            "userName": "ann",
+            "email": "ann@example.com",
+            "homepage": "https://example.com/ann",
+            "age": 30,
+            "lines": [{"quantity": 1}, {"quantity": 2}]
+        }"#,
+    )
+    .unwrap();
+
+    account.validate().unwrap();
+    assert_eq!(account.lines[1].quantity, 2);
+}
+
+#[test]
+fn rule_errors_are_keyed_by_wire_names() {
+    let account = Account {
+        user_name: "Ann Smith".to_string(),
+        email: "ann@localhost".to_string(),
+        homepage: Some("example.com".to_string()),
+        age: 17,
+        lines: vec![Line { quantity: 0 }],
+    };
+
+    let errors = account.validate().unwrap_err();
+
+    assert_eq!(
+        errors.messages(),
+        messages(&[
+            (
+                "age",
+                "invalid value: `17`, expected a value between 18 and 130"
+            ),
+            ("age", "must be even"),
+            (
+                "email",
+                "invalid value: string \"ann@localhost\", expected an email address"
+            ),
+            (
+                "homepage",
+                "invalid value: string \"example.com\", expected a URL"
+            ),
+            ("lines", "invalid length 1, expected a length of 2"),
+            (
+                "lines[0].quantity",
+                "invalid value: `0`, expected a value of at least 1"
+            ),
+            (
+                "userName",
+                "invalid length 9, expected a length between 3 and 8"
+            ),
+        ])
+    );
+    assert_eq!(
+        errors.get("homepage").unwrap()[0].kind,
+        ErrorKind::InvalidValue
+    );
+}
+
+#[test]
+fn errors_of_a_field_are_kept_in_order() {
+    let mut errors = StructValidator::new();
+    errors.insert("password", "too short");
+    errors.insert("password", "must contain a digit");
+    assert_eq!(
+        errors.messages()["password"],
+        ["too short", "must contain a digit"]
+    );
+
+    let json = r#"{"userName": "ann", "email": "ann@example.com", "age": 17, "lines": [{"quantity": 1}, {"quantity": 1}]}"#;
+    let error = serde_json::from_str::<Account>(json).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+    let kinds: Vec<_> = errors.get("age").unwrap().iter().map(|e| e.kind).collect();
+    assert_eq!(kinds, [ErrorKind::InvalidValue, ErrorKind::Custom]);
+    assert_eq!(
+        errors.messages()["age"],
+        [
+            "invalid value: `17`, expected a value between 18 and 130",
+            "must be even"
+        ]
+    );
+}
+
+#[test]
+fn absent_options_are_not_checked() {
+    let account = Account {
+        user_name: "ann".to_string(),
+        email: "ann@example.com".to_string(),
+        homepage: None,
+        age: 20,
+        lines: vec![Line { quantity: 1 }, Line { quantity: 1 }],
+    };
+
+    assert!(account.validate().is_ok());
+}
+
+#[test]
+fn deserialization_reports_rule_and_type_errors_together() {
+    let error = serde_json::from_str::<Account>(
+        r#"{"userName": "A", "email": 3, "age": 17, "lines": [{"quantity": 0}]}"#,
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        messages(&[
+            (
+                "age",
+                "invalid value: `17`, expected a value between 18 and 130"
+            ),
+            ("age", "must be even"),
+            ("email", "invalid type: integer `3`, expected a string"),
+            (
+                "lines[0].quantity",
+                "invalid value: `0`, expected a value of at least 1"
+            ),
+            (
+                "userName",
+                "invalid length 1, expected a length between 3 and 8"
+            ),
+        ])
+    );
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Comment<'a> {
+    #[validate(length(min = 3))]
+    author: &'a str,
+    #[validate(email)]
+    email: &'a str,
+}
+
+#[test]
+fn borrowed_fields_are_checked() {
+    let json = r#"{"author": "ann", "email": "ann@example.com"}"#;
+    let comment: Comment = serde_json::from_str(json).unwrap();
+    assert!(comment.validate().is_ok());
+
+    let error = serde_json::from_str::<Comment>(r#"{"author": "al", "email": "al"}"#).unwrap_err();
+    let expected = messages(&[
+        (
+            "author",
+            "invalid length 2, expected a length of at least 3",
+        ),
+        (
+            "email",
+            "invalid value: string \"al\", expected an email address",
+        ),
+    ]);
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        expected
+    );
+    let comment = Comment {
+        author: "al",
+        email: "al",
+    };
+    assert_eq!(comment.validate().unwrap_err().messages(), expected);
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Quantity(#[validate(range(min = 1))] u32);
+
+fn not_reserved(tag: &Tag) -> Result<(), FieldError> {
+    if tag.name == "admin" {
+        Err(FieldError::custom("is reserved"))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[serde(transparent)]
+#[validate(custom = "not_reserved")]
+struct Tag {
+    #[validate(length(max = 5))]
+    name: String,
+}
+
+/// Only implements plain `Deserialize`, so it is checked by `nested` alone.
+#[derive(Debug, Deserialize)]
+struct Sku {
+    code: String,
+}
+
+impl Validate for Sku {
+    fn validate(&self) -> Result<(), StructValidator> {
+        if self.code.is_empty() {
+            Err(StructValidator::new().with_field_error("code", FieldError::custom("is empty")))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Product {
+    quantity: Quantity,
+    tags: Vec<Tag>,
+    #[validate(nested)]
+    sku: Sku,
+}
+
+#[test]
+fn newtype_transparent_and_nested_rules_run_when_deserializing() {
+    let json = r#"{"quantity": 2, "tags": ["new"], "sku": {"code": "A1"}}"#;
+    let product: Product = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        (product.quantity.0, product.tags[0].name.as_str()),
+        (2, "new")
+    );
+    assert_eq!(product.sku.code, "A1");
+
+    let json = r#"{"quantity": 0, "tags": ["admin", "clearance"], "sku": {"code": ""}}"#;
+    let error = serde_json::from_str::<Product>(json).unwrap_err();
+    let expected = messages(&[
+        (
+            "quantity",
+            "invalid value: `0`, expected a value of at least 1",
+        ),
+        ("sku.code", "is empty"),
+        ("tags[0]", "is reserved"),
+        (
+            "tags[1]",
+            "invalid length 9, expected a length of at most 5",
+        ),
+    ]);
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        expected
+    );
+    assert_eq!(
+        Quantity(0).validate().unwrap_err().messages(),
+        messages(&[(
+            StructValidator::ROOT,
+            "invalid value: `0`, expected a value of at least 1",
+        )])
+    );
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+enum Order {
+    Units(#[validate(range(min = 1, max = 5))] u32),
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[serde(tag = "kind", content = "data")]
+enum Refund {
+    Partial(#[validate(range(max = 100))] u8),
+}
+
+#[test]
+fn newtype_variant_rules_run_when_deserializing() {
+    let Order::Units(units) = serde_json::from_str(r#"{"Units": 3}"#).unwrap();
+    assert_eq!(units, 3);
+    let Refund::Partial(percent) =
+        serde_json::from_str(r#"{"kind": "Partial", "data": 40}"#).unwrap();
+    assert_eq!(percent, 40);
+
+    let error = serde_json::from_str::<Order>(r#"{"Units": 99}"#).unwrap_err();
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        messages(&[(
+            "Units",
+            "invalid value: `99`, expected a value between 1 and 5",
+        )])
+    );
+    let error = serde_json::from_str::<Refund>(r#"{"kind": "Partial", "data": 140}"#).unwrap_err();
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        messages(&[(
+            "data",
+            "invalid value: `140`, expected a value of at most 100"
+        )])
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_rules_match_the_pattern() {
+    #[derive(Debug, Validate, ValidatedDeserialize)]
+    struct Handle {
+        #[validate(regex = "^[a-z]+$")]
+        name: String,
+    }
+
+    assert!(serde_json::from_str::<Handle>(r#"{"name": "ann"}"#).is_ok());
+    let error = serde_json::from_str::<Handle>(r#"{"name": "Ann Smith"}"#).unwrap_err();
+    let expected = messages(&[(
+        "name",
+        "invalid value: string \"Ann Smith\", expected a string matching `^[a-z]+$`",
+    )]);
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        expected
+    );
+    let handle = Handle {
+        name: "Ann Smith".to_string(),
+    };
+    assert_eq!(handle.validate().unwrap_err().messages(), expected);
+}
+
+fn ordered(window: &Window) -> Result<(), &'static str> {
+    if window.start <= window.end {
+        Ok(())
+    } else {
+        Err("start must not be after end")
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[validate(custom = "ordered")]
+struct Window {
+    #[validate(range(max = 24))]
+    start: u8,
+    end: u8,
+}
+
+#[test]
+fn struct_rules_run_once_the_fields_are_parsed() {
+    let window: Window = serde_json::from_str(r#"{"start": 8, "end": 18}"#).unwrap();
+    assert!(window.validate().is_ok());
+
+    let error = serde_json::from_str::<Window>(r#"{"start": 30, "end": 2}"#).unwrap_err();
+    let expected = messages(&[
+        (StructValidator::ROOT, "start must not be after end"),
+        (
+            "start",
+            "invalid value: `30`, expected a value of at most 24",
+        ),
+    ]);
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        expected
+    );
+    let window = Window { start: 30, end: 2 };
+    assert_eq!(window.validate().unwrap_err().messages(), expected);
+
+    let error = serde_json::from_str::<Window>(r#"{"start": 1}"#).unwrap_err();
+    assert_eq!(
+        StructValidator::try_from_de_error(error)
+            .unwrap()
+            .messages(),
+        messages(&[("end", "field is missing")])
+    );
+}
+
+fn passwords_match(form: &Registration) -> Result<(), StructValidator> {
+    if form.password == form.password_confirm {
+        Ok(())
+    } else {
+        Err(StructValidator::new().with_field_error(
+            "password_confirm",
+            FieldError::custom("must equal password"),
+        ))
+    }
+}
+
+fn dates_ordered(form: &Registration) -> Result<(), StructValidator> {
+    if form.start_date < form.end_date {
+        Ok(())
+    } else {
+        Err(StructValidator::new()
+            .with_field_error("end_date", FieldError::custom("must be after start_date")))
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[validate(schema(function = "passwords_match", fields(password, password_confirm)))]
+#[validate(schema = "dates_ordered")]
+struct Registration {
+    #[validate(length(min = 8))]
+    password: String,
+    password_confirm: String,
+    start_date: u32,
+    #[validate(range(max = 100))]
+    end_date: u32,
+}
+
+fn registration_errors(json: &str) -> BTreeMap<String, Vec<String>> {
+    let error = serde_json::from_str::<Registration>(json).unwrap_err();
+    StructValidator::try_from_de_error(error)
+        .unwrap()
+        .messages()
+}
+
+#[test]
+fn schema_hooks_report_on_any_field() {
+    let expected = messages(&[
+        ("end_date", "must be after start_date"),
+        ("password_confirm", "must equal password"),
+    ]);
+    assert_eq!(
+        registration_errors(
+            r#"{"password": "secret-1", "password_confirm": "secret-2", "start_date": 9, "end_date": 3}"#
+        ),
+        expected
+    );
+
+    let registration = Registration {
+        password: "secret-1".to_string(),
+        password_confirm: "secret-2".to_string(),
+        start_date: 9,
+        end_date: 3,
+    };
+    assert_eq!(registration.validate().unwrap_err().messages(), expected);
+}
+
+#[test]
+fn schema_hooks_skip_fields_that_already_failed() {
+    assert_eq!(
+        registration_errors(
+            r#"{"password": "short", "password_confirm": "other", "start_date": 150, "end_date": 120}"#
+        ),
+        messages(&[
+            (
+                "end_date",
+                "invalid value: `120`, expected a value of at most 100"
+            ),
+            (
+                "password",
+                "invalid length 5, expected a length of at least 8"
+            ),
+        ])
+    );
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Invoice {
+    country: String,
+    #[validate(
+        required_if(field = country, value = "DE"),
+        required_if(field = country, value = "FR")
+    )]
+    vat_number: Option<String>,
+    delivery: Option<String>,
+    #[validate(required_unless(field = delivery, value = "pickup"))]
+    address: Option<String>,
+    phone: Option<String>,
+    #[validate(required_with(phone))]
+    phone_country: Option<String>,
+}
+
+#[test]
+fn conditional_requirements_explain_their_condition() {
+    let invoice: Invoice =
+        serde_json::from_str(r#"{"country": "US", "delivery": "pickup", "phone_country": "US"}"#)
+            .unwrap();
+    assert!(invoice.validate().is_ok());
+
+    let error =
+        serde_json::from_str::<Invoice>(r#"{"country": "FR", "phone": "555"}"#).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+
+    assert_eq!(
+        errors.messages(),
+        messages(&[
+            (
+                "address",
+                "field is missing, it is required unless `delivery` is \"pickup\""
+            ),
+            (
+                "phone_country",
+                "field is missing, it is required when `phone` is present"
+            ),
+            (
+                "vat_number",
+                "field is missing, it is required when `country` is \"FR\""
+            ),
+        ])
+    );
+    assert_eq!(
+        errors.get("vat_number").unwrap()[0].kind,
+        ErrorKind::Missing
+    );
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+struct Shipment {
+    country: Option<String>,
+    #[validate(required_if(field = country, value = ["DE", "FR", "IT"]))]
+    vat_number: Option<String>,
+    weight: u32,
+    #[validate(required_unless(field = weight, value = [0, 1]))]
+    carrier: Option<String>,
+}
+
+#[test]
+fn conditional_requirements_accept_a_list_of_values() {
+    for json in [
+        r#"{"country": "US", "weight": 1}"#,
+        r#"{"weight": 0}"#,
+        r#"{"country": "IT", "vat_number": "IT1", "weight": 5, "carrier": "post"}"#,
+    ] {
+        let shipment: Shipment = serde_json::from_str(json).unwrap();
+        assert!(shipment.validate().is_ok());
+    }
+
+    for country in ["DE", "FR", "IT"] {
+        let json = format!(r#"{{"country": "{}", "weight": 2}}"#, country);
+        let error = serde_json::from_str::<Shipment>(&json).unwrap_err();
+        assert_eq!(
+            StructValidator::try_from_de_error(error)
+                .unwrap()
+                .messages(),
+            messages(&[
+                (
+                    "carrier",
+                    "field is missing, it is required unless `weight` is one of [0, 1]"
+                ),
+                (
+                    "vat_number",
+                    "field is missing, it is required when `country` is one of [\"DE\", \"FR\", \"IT\"]"
+                ),
+            ])
+        );
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[validate(exactly_one_of(email, phone), at_most_one_of(file, link))]
+struct Submission {
+    email: Option<String>,
+    phone: Option<String>,
+    file: Option<String>,
+    link: Option<String>,
+}
+
+#[test]
+fn field_groups_are_reported_on_every_field() {
+    let submission: Submission = serde_json::from_str(r#"{"phone": "555"}"#).unwrap();
+    assert!(submission.validate().is_ok());
+
+    let error =
+        serde_json::from_str::<Submission>(r#"{"file": "a.txt", "link": "b"}"#).unwrap_err();
+    let errors = StructValidator::try_from_de_error(error).unwrap();
+    assert_eq!(
+        errors.messages(),
+        messages(&[
+            ("email", "one of `email`, `phone` is required"),
+            ("file", "only one of `file`, `link` may be set"),
+            ("link", "only one of `file`, `link` may be set"),
+            ("phone", "one of `email`, `phone` is required"),
+        ])
+    );
+    assert_eq!(errors.get("link").unwrap()[0].kind, ErrorKind::Conflict);
+
+    let submission = Submission {
+        email: Some("a@b.c".to_string()),
+        phone: Some("555".to_string()),
+        file: None,
+        link: None,
+    };
+    let errors = submission.validate().unwrap_err();
+    assert_eq!(errors.get("email").unwrap()[0].kind, ErrorKind::Conflict);
+    assert_eq!(
+        errors.get("phone").unwrap()[0].message,
+        "only one of `email`, `phone` may be set"
+    );
+}
+
+fn known_kind(customer: &Customer) -> Result<(), FieldError> {
+    if ["biz", "home"].contains(&customer.kind.as_str()) {
+        Ok(())
+    } else {
+        Err(FieldError::custom("unknown kind"))
+    }
+}
+
+#[derive(Debug, ValidatedDeserialize)]
+#[validate(partial, at_least_one_of(email, phone), custom = "known_kind")]
+struct Customer {
+    kind: String,
+    email: Option<String>,
+    phone: Option<String>,
+    #[validate(required_if(field = kind, value = "biz"))]
+    vat: Option<String>,
+}
+
+#[test]
+fn partial_deserialization_runs_struct_rules() {
+    for (json, expected) in [
+        (
+            r#"{"kind": "biz"}"#,
+            messages(&[
+                ("email", "one of `email`, `phone` is required"),
+                ("phone", "one of `email`, `phone` is required"),
+                (
+                    "vat",
+                    "field is missing, it is required when `kind` is \"biz\"",
+                ),
+            ]),
+        ),
+        (
+            r#"{"kind": "shop", "email": "a@b.c"}"#,
+            messages(&[(StructValidator::ROOT, "unknown kind")]),
+        ),
+    ] {
+        let error = serde_json::from_str::<Customer>(json).unwrap_err();
+        assert_eq!(
+            StructValidator::try_from_de_error(error)
+                .unwrap()
+                .messages(),
+            expected
+        );
+
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let (_, errors) = Customer::deserialize_partial(&mut deserializer).unwrap();
+        assert_eq!(errors.messages(), expected);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(r#"{"kind": "biz"}"#);
+    let (customer, _) = Customer::deserialize_partial(&mut deserializer).unwrap();
+    assert_eq!(customer.kind.unwrap(), "biz");
+    assert!(customer.email.is_err() && customer.phone.is_err());
+    assert_eq!(
+        customer
+            .vat
+            .unwrap_err()
+            .get(StructValidator::ROOT)
+            .unwrap()[0]
+            .kind,
+        ErrorKind::Missing
+    );
+
+    let customer: Customer = serde_json::from_str(r#"{"kind": "home", "phone": "555"}"#).unwrap();
+    assert_eq!(
+        (customer.email, customer.phone.as_deref(), customer.vat),
+        (None, Some("555"), None)
+    );
+}
+
+struct Settings {
+    currencies: Vec<&'static str>,
+    current_user: &'static str,
+}
+
+fn supported_currency(currency: &str, settings: &Settings) -> Result<(), String> {
+    if settings.currencies.contains(&currency) {
+        Ok(())
+    } else {
+        Err(format!("`{}` is not supported", currency))
+    }
+}
+
+fn not_to_self(transfer: &Transfer, settings: &Settings) -> Result<(), StructValidator> {
+    if transfer.recipient == settings.current_user {
+        Err(StructValidator::new().with_field_error(
+            "recipient",
+            FieldError::custom("cannot transfer to yourself"),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[validate(context = "Settings", schema_with = "not_to_self")]
+struct Transfer {
+    #[validate(length(equal = 3), custom_with = "supported_currency")]
+    currency: String,
+    recipient: String,
+}
+
+#[test]
+fn rules_receive_the_validation_context() {
+    let settings = Settings {
+        currencies: vec!["EUR", "USD"],
+        current_user: "ann",
+    };
+    let transfer: Transfer =
+        serde_json::from_str(r#"{"currency": "EUR", "recipient": "bob"}"#).unwrap();
+    assert!(transfer.validate_with(&settings).is_ok());
+
+    let transfer = Transfer {
+        currency: "GBP".to_string(),
+        recipient: "ann".to_string(),
+    };
+    assert!(transfer.validate().is_ok());
+    assert_eq!(
+        transfer.validate_with(&settings).unwrap_err().messages(),
+        messages(&[
+            ("currency", "`GBP` is not supported"),
+            ("recipient", "cannot transfer to yourself"),
+        ])
+    );
+
+    let transfer = Transfer {
+        currency: "EURO".to_string(),
+        recipient: "bob".to_string(),
+    };
+    assert_eq!(
+        transfer.validate_with(&settings).unwrap_err().messages(),
+        messages(&[("currency", "invalid length 4, expected a length of 3")])
+    );
+}
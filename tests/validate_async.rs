@@ -0,0 +1,214 @@
+#![cfg(feature = "async")]
+
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::task::{Context, Poll};
+
+use futures::executor::block_on;
+use futures::future::poll_fn;
+use futures::task::noop_waker_ref;
+use struct_validator::{
+    FieldError, StructValidator, Validate, ValidateAsync, ValidatedDeserialize,
+};
+
+use common::messages;
+
+mod common;
+
+trait Users {
+    fn is_taken(&self, username: &str) -> bool;
+    fn team_exists(&self, team: u32) -> bool;
+}
+
+/// Stub repository recording the lookups it serves.
+struct InMemoryUsers {
+    taken: Vec<&'static str>,
+    teams: Vec<u32>,
+    lookups: RefCell<Vec<String>>,
+}
+
+impl InMemoryUsers {
+    fn new() -> Self {
+        Self {
+            taken: vec!["root", "admin"],
+            teams: vec![1, 2],
+            lookups: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl Users for InMemoryUsers {
+    fn is_taken(&self, username: &str) -> bool {
+        self.lookups.borrow_mut().push(username.to_string());
+        self.taken.contains(&username)
+    }
+
+    fn team_exists(&self, team: u32) -> bool {
+        self.teams.contains(&team)
+    }
+}
+
+async fn username_free(username: &str, users: &dyn Users) -> Result<(), FieldError> {
+    if users.is_taken(username) {
+        Err(FieldError::custom("username is taken"))
+    } else {
+        Ok(())
+    }
+}
+
+async fn team_exists(member: &Member, users: &dyn Users) -> Result<(), StructValidator> {
+    if users.team_exists(member.team) {
+        Ok(())
+    } else {
+        Err(StructValidator::new()
+            .with_field_error("team", FieldError::custom("team does not exist")))
+    }
+}
+
+#[derive(Debug, Validate, ValidatedDeserialize)]
+#[validate(context = "dyn Users", async_schema = "team_exists")]
+struct Member {
+    #[validate(length(min = 4), async_custom = "username_free")]
+    username: String,
+    #[validate(async_custom = "username_free")]
+    nickname: Option<String>,
+    team: u32,
+}
+
+#[test]
+fn async_rules_use_the_context() {
+    let users = InMemoryUsers::new();
+    let member: Member =
+        serde_json::from_str(r#"{"username": "alice", "nickname": "al", "team": 1}"#).unwrap();
+    assert!(block_on(member.validate_async(&users)).is_ok());
+
+    let member = Member {
+        username: "admin".to_string(),
+        nickname: Some("root".to_string()),
+        team: 7,
+    };
+    assert!(member.validate().is_ok());
+    assert_eq!(
+        block_on(member.validate_async(&users))
+            .unwrap_err()
+            .messages(),
+        messages(&[
+            ("nickname", "username is taken"),
+            ("team", "team does not exist"),
+            ("username", "username is taken"),
+        ])
+    );
+}
+
+#[test]
+fn async_rules_skip_fields_that_already_failed() {
+    let users = InMemoryUsers::new();
+    let member = Member {
+        username: "bob".to_string(),
+        nickname: None,
+        team: 2,
+    };
+
+    assert_eq!(
+        block_on(member.validate_async(&users))
+            .unwrap_err()
+            .messages(),
+        messages(&[(
+            "username",
+            "invalid length 3, expected a length of at least 4"
+        )])
+    );
+    assert!(users.lookups.borrow().is_empty());
+}
+
+/// Context letting lookups wait until both of them started.
+struct Rendezvous {
+    arrived: Cell<usize>,
+}
+
+async fn meet(_: &u32, rendezvous: &Rendezvous) -> Result<(), FieldError> {
+    rendezvous.arrived.set(rendezvous.arrived.get() + 1);
+    poll_fn(|_| {
+        if rendezvous.arrived.get() == 2 {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[derive(Debug, Validate)]
+#[validate(context = "Rendezvous")]
+struct Pair {
+    #[validate(async_custom = "meet")]
+    first: u32,
+    #[validate(async_custom = "meet")]
+    second: u32,
+}
+
+#[test]
+fn async_rules_run_concurrently() {
+    let rendezvous = Rendezvous {
+        arrived: Cell::new(0),
+    };
+    let pair = Pair {
+        first: 1,
+        second: 2,
+    };
+    let mut validation = Box::pin(pair.validate_async(&rendezvous));
+    let mut context = Context::from_waker(noop_waker_ref());
+
+    // Run one after the other, the first lookup would wait for the second one forever.
+    let result = (0..3).find_map(|_| match validation.as_mut().poll(&mut context) {
+        Poll::Ready(result) => Some(result),
+        Poll::Pending => None,
+    });
+    assert!(matches!(result, Some(Ok(()))));
+    assert_eq!(rendezvous.arrived.get(), 2);
+}
+
+/// Context safe to share between threads, unlike the ones above.
+struct Directory {
+    invited: Vec<&'static str>,
+}
+
+async fn invited(email: &str, directory: &Directory) -> Result<(), FieldError> {
+    if directory.invited.contains(&email) {
+        Ok(())
+    } else {
+        Err(FieldError::custom("was not invited"))
+    }
+}
+
+#[derive(Debug, Validate)]
+#[validate(context = "Directory")]
+struct Guest {
+    #[validate(email, async_custom = "invited")]
+    email: String,
+}
+
+fn assert_send<T: Send>(value: T) -> T {
+    value
+}
+
+#[test]
+fn async_validation_is_send_with_a_sync_context() {
+    let directory = Directory {
+        invited: vec!["ann@example.com"],
+    };
+    let guest = Guest {
+        email: "ann@example.com".to_string(),
+    };
+    assert!(block_on(assert_send(guest.validate_async(&directory))).is_ok());
+
+    let guest = Guest {
+        email: "bob@example.com".to_string(),
+    };
+    let validation = assert_send(guest.validate_async(&directory));
+    let result = std::thread::scope(|scope| scope.spawn(|| block_on(validation)).join().unwrap());
+    assert_eq!(
+        result.unwrap_err().messages(),
+        messages(&[("email", "was not invited")])
+    );
+}